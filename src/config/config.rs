@@ -1,10 +1,14 @@
-use serde::{Deserialize, Serialize};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use tokio::fs;
 use serde_saphyr::{from_str, to_string};
 
 use crate::errors::{Result, Error, ErrorKind};
 use crate::crypto::{generate_keypair, secret_key_to_public_key};
+use crate::scroll::{Device, KeyChord, ScrollSource, UINPUT_MAX_NAME_SIZE};
+use crate::utils::glob_match;
 use super::constants::DEFAULT_BIND;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,7 +21,7 @@ pub struct Config {
     pub peers: Vec<PeerConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub bind: String,
     #[serde(default)]
@@ -36,6 +40,38 @@ pub struct ServerConfig {
     pub scroll_destination: bool,
     #[serde(default)]
     pub scroll_input_devices: Vec<InputDevice>,
+    #[serde(default)]
+    pub pointer_sharing: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer_hotkey: Option<String>,
+    #[serde(default)]
+    pub keyboard_sharing: bool,
+    #[serde(default)]
+    pub keyboard_input_devices: Vec<InputDevice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyboard_panic_hotkey: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_hotkeys: Option<String>,
+    #[serde(default)]
+    pub gesture_sharing: bool,
+    #[serde(default)]
+    pub gesture_actions: Vec<GestureAction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scroll_flush_interval: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scroll_latency_budget: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kinetic_scroll: Option<KineticScroll>,
+    #[serde(default)]
+    pub scroll_transform: Vec<ScrollTransform>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scroll_output: Option<ScrollOutput>,
+    #[serde(default)]
+    pub scroll_mirror: bool,
+    #[serde(default)]
+    pub focus_activation: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_bind: Option<String>,
 }
 
 impl ServerConfig {
@@ -50,7 +86,7 @@ impl ServerConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PeerConfig {
     pub address: String,
     pub public_key: String,
@@ -62,6 +98,26 @@ pub struct PeerConfig {
     pub scroll_source: bool,
     #[serde(default)]
     pub scroll_destination: bool,
+    #[serde(default)]
+    pub session: bool,
+    #[serde(default)]
+    pub mirror: bool,
+    #[serde(default)]
+    pub scroll_transform: Vec<ScrollTransform>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer_edge: Option<ScreenEdge>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_window: Option<FocusWindow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FocusWindow {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wm_class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
 }
 
 impl Default for PeerConfig {
@@ -73,33 +129,373 @@ impl Default for PeerConfig {
             clipboard_destination: true,
             scroll_source: false,
             scroll_destination: false,
+            session: false,
+            mirror: false,
+            scroll_transform: Vec::new(),
+            pointer_edge: None,
+            focus_window: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreenEdge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GestureKind {
+    Swipe,
+    Pinch,
+    Hold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GestureDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+    In,
+    Out,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GestureActionKind {
+    Zoom,
+    Keys,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GestureAction {
+    pub gesture: GestureKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingers: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<GestureDirection>,
+    pub action: GestureActionKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KineticScroll {
+    #[serde(default = "default_kinetic_decay")]
+    pub decay: u64,
+    #[serde(default = "default_kinetic_min_velocity")]
+    pub min_velocity: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InputDevice {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_glob: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_regex: Option<NameRegex>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor_id: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_id: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phys: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uniq: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
     #[serde(default = "default_scroll_reverse")]
     pub scroll_reverse: bool,
     #[serde(default = "default_scroll_modifier")]
     pub scroll_modifier: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scroll_transform: Vec<ScrollTransform>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub virtual_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub virtual_id: Option<VirtualDeviceId>,
+    #[serde(default)]
+    pub scroll_target: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activation: Option<ScrollActivation>,
+}
+
+// Compiled when the config is loaded, device matching runs on every hotplug
+#[derive(Debug, Clone)]
+pub struct NameRegex(Regex);
+
+impl NameRegex {
+    pub fn is_match(&self, name: &str) -> bool {
+        self.0.is_match(name)
+    }
+}
+
+impl PartialEq for NameRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Serialize for NameRegex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for NameRegex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let regex = String::deserialize(deserializer)?;
+        Regex::new(&regex)
+            .map(Self)
+            .map_err(|e| serde::de::Error::custom(
+                format!("invalid device name regex {:?}: {}", regex, e)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VirtualDeviceId {
+    pub bustype: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+}
+
+impl VirtualDeviceId {
+    pub fn as_array(&self) -> [u16; 4] {
+        [self.bustype, self.vendor, self.product, self.version]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivationTrigger {
+    #[default]
+    Scroll,
+    Motion,
+    Button,
+    Region,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivationRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ActivationRegion {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (dx, dy) = (x as i64 - self.x as i64, y as i64 - self.y as i64);
+        (0..self.width as i64).contains(&dx) && (0..self.height as i64).contains(&dy)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrollActivation {
+    #[serde(default)]
+    pub trigger: ActivationTrigger,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<ActivationRegion>,
+    #[serde(default)]
+    pub debounce: u64,
+    #[serde(default = "default_activation_rate_limit")]
+    pub rate_limit: u64,
+}
+
+impl Default for ScrollActivation {
+    fn default() -> Self {
+        Self {
+            trigger: ActivationTrigger::default(),
+            region: None,
+            debounce: 0,
+            rate_limit: default_activation_rate_limit(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrollTransform {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ScrollSource>,
+    #[serde(default = "default_scroll_multiplier")]
+    pub multiplier_x: f64,
+    #[serde(default = "default_scroll_multiplier")]
+    pub multiplier_y: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub curve: Option<ScrollCurve>,
+    #[serde(default)]
+    pub swap_axes: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub axis_lock: Option<AxisLock>,
+    #[serde(default)]
+    pub deadzone: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ScrollCurve {
+    Power {
+        exponent: f64,
+        #[serde(default = "default_curve_range")]
+        range: f64,
+    },
+    Bezier {
+        points: [f64; 4],
+        #[serde(default = "default_curve_range")]
+        range: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollOutput {
+    Both,
+    HiRes,
+    Discrete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AxisLock {
+    Horizontal,
+    Vertical,
+    Dominant,
 }
 
 impl Default for InputDevice {
     fn default() -> Self {
         Self {
             name: None,
+            name_glob: None,
+            name_regex: None,
             path: None,
+            vendor_id: None,
+            product_id: None,
+            phys: None,
+            uniq: None,
+            properties: BTreeMap::new(),
             scroll_reverse: default_scroll_reverse(),
             scroll_modifier: default_scroll_modifier(),
+            scroll_transform: Vec::new(),
+            virtual_name: None,
+            virtual_id: None,
+            scroll_target: false,
+            activation: None,
+        }
+    }
+}
+
+impl InputDevice {
+    pub fn matches(&self, device: &Device) -> bool {
+        let properties = (!self.properties.is_empty()).then(|| {
+            self.properties.iter().all(|(k, v)| device.properties.get(k) == Some(v))
+        });
+
+        // Name and path pick the same device either way, either one matching
+        // is enough, every other field set narrows the match
+        let name = self.name.as_ref().map(|n| n.eq_ignore_ascii_case(&device.name));
+        let path = self.path.as_ref().map(|p| *p == device.path);
+        let name_or_path = name.into_iter().chain(path).reduce(|a, b| a || b);
+
+        let mut matchers = [
+            name_or_path,
+            self.name_glob.as_ref().map(|g| glob_match(g, &device.name)),
+            self.name_regex.as_ref().map(|r| r.is_match(&device.name)),
+            self.vendor_id.map(|v| v == device.vendor_id),
+            self.product_id.map(|p| p == device.product_id),
+            self.phys.as_ref().map(|p| device.phys.as_ref() == Some(p)),
+            self.uniq.as_ref().map(|u| device.uniq.as_ref() == Some(u)),
+            properties,
+        ].into_iter().flatten().peekable();
+
+        matchers.peek().is_some() && matchers.all(|m| m)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(name) = &self.virtual_name
+            && (name.is_empty() || name.len() >= UINPUT_MAX_NAME_SIZE) {
+            return Err(Error::new(ErrorKind::Parse)
+                .with_msg("config: Virtual device name must be between 1 and 79 bytes")
+                .with_ctx("virtual_name", name));
+        }
+
+        if let Some(activation) = &self.activation {
+            match (activation.trigger, &activation.region) {
+                (ActivationTrigger::Region, None) => {
+                    return Err(Error::new(ErrorKind::Parse)
+                        .with_msg("config: Region activation trigger requires a region"));
+                }
+                (_, Some(region)) if region.width == 0 || region.height == 0 => {
+                    return Err(Error::new(ErrorKind::Parse)
+                        .with_msg("config: Activation region must have a positive size")
+                        .with_ctx("width", region.width)
+                        .with_ctx("height", region.height));
+                }
+                _ => {}
+            }
         }
+
+        Ok(())
     }
 }
 
+impl ScrollTransform {
+    fn validate(&self) -> Result<()> {
+        if self.deadzone < 0.0 {
+            return Err(Error::new(ErrorKind::Parse)
+                .with_msg("config: Scroll transform deadzone cannot be negative")
+                .with_ctx("deadzone", self.deadzone));
+        }
+
+        match &self.curve {
+            Some(ScrollCurve::Power { exponent, range }) if *exponent <= 0.0 || *range <= 0.0 => {
+                Err(Error::new(ErrorKind::Parse)
+                    .with_msg("config: Power curve exponent and range must be positive")
+                    .with_ctx("exponent", exponent)
+                    .with_ctx("range", range))
+            }
+            Some(ScrollCurve::Bezier { points, range }) if *range <= 0.0
+                || !(0.0..=1.0).contains(&points[0])
+                || !(0.0..=1.0).contains(&points[2]) => {
+                Err(Error::new(ErrorKind::Parse)
+                    .with_msg("config: Bezier curve x points must be within 0 and 1 and range positive")
+                    .with_ctx("range", range))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+const fn default_kinetic_decay() -> u64 {
+    325
+}
+
+const fn default_kinetic_min_velocity() -> f64 {
+    10.0
+}
+
+const fn default_scroll_multiplier() -> f64 {
+    1.0
+}
+
+const fn default_curve_range() -> f64 {
+    15.0
+}
+
+const fn default_activation_rate_limit() -> u64 {
+    250
+}
+
 const fn default_scroll_reverse() -> bool {
     true
 }
@@ -167,6 +563,22 @@ impl Config {
                 scroll_source: false,
                 scroll_destination: false,
                 scroll_input_devices: Vec::new(),
+                pointer_sharing: false,
+                pointer_hotkey: None,
+                keyboard_sharing: false,
+                keyboard_input_devices: Vec::new(),
+                keyboard_panic_hotkey: None,
+                peer_hotkeys: None,
+                gesture_sharing: false,
+                gesture_actions: Vec::new(),
+                scroll_flush_interval: None,
+                scroll_latency_budget: None,
+                kinetic_scroll: None,
+                scroll_transform: Vec::new(),
+                scroll_output: None,
+                scroll_mirror: false,
+                focus_activation: false,
+                metrics_bind: None,
             },
             peers: Vec::new(),
         };
@@ -197,11 +609,19 @@ impl Config {
         self.modified
     }
 
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     pub fn set_keypair(&mut self, private_key: String, public_key: String) {
         self.server.private_key = private_key;
         self.server.public_key = public_key;
     }
 
+    pub fn set_scroll_mirror(&mut self, enabled: bool) {
+        self.server.scroll_mirror = enabled;
+    }
+
     fn normalize(&mut self) -> Result<()> {
         if self.server.private_key.is_empty() {
             let (secret, public) = generate_keypair();
@@ -231,6 +651,52 @@ impl Config {
                 .with_msg("config: Public key cannot be empty"));
         }
 
+        if let Some(hotkey) = &self.server.pointer_hotkey {
+            KeyChord::parse(hotkey)?;
+        }
+
+        if let Some(hotkey) = &self.server.keyboard_panic_hotkey {
+            KeyChord::parse(hotkey)?;
+        }
+
+        if let Some(prefix) = &self.server.peer_hotkeys {
+            KeyChord::parse(&format!("{}+0", prefix))?;
+        }
+
+        for (i, action) in self.server.gesture_actions.iter().enumerate() {
+            match action.action {
+                GestureActionKind::Zoom if action.gesture != GestureKind::Pinch => {
+                    return Err(Error::new(ErrorKind::Parse)
+                        .with_msg("config: Zoom gesture action requires a pinch gesture")
+                        .with_ctx("action_index", i));
+                }
+                GestureActionKind::Keys => {
+                    let keys = action.keys.as_ref().ok_or_else(|| Error::new(ErrorKind::Parse)
+                        .with_msg("config: Keys gesture action requires keys")
+                        .with_ctx("action_index", i))?;
+                    KeyChord::parse(keys)?;
+                }
+                _ => {}
+            }
+        }
+
+        let input_devices = self.server.scroll_input_devices.iter()
+            .chain(&self.server.keyboard_input_devices);
+        for device in input_devices {
+            device.validate()?;
+        }
+
+        let device_transforms = self.server.scroll_input_devices.iter()
+            .flat_map(|d| &d.scroll_transform);
+        let peer_transforms = self.peers.iter()
+            .flat_map(|p| &p.scroll_transform);
+        let transforms = self.server.scroll_transform.iter()
+            .chain(device_transforms)
+            .chain(peer_transforms);
+        for transform in transforms {
+            transform.validate()?;
+        }
+
         for (i, peer) in self.peers.iter().enumerate() {
             if peer.address.is_empty() {
                 return Err(Error::new(ErrorKind::Parse)
@@ -243,6 +709,12 @@ impl Config {
                     .with_msg("config: Peer public key cannot be empty")
                     .with_ctx("peer_index", i));
             }
+
+            if peer.mirror && !peer.scroll_destination {
+                return Err(Error::new(ErrorKind::Parse)
+                    .with_msg("config: Mirror peer must be a scroll destination")
+                    .with_ctx("peer_index", i));
+            }
         }
 
         Ok(())
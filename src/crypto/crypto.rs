@@ -9,6 +9,11 @@ use super::store::KeyStore;
 
 const NONCE_SIZE: usize = 24;
 
+// Random value a peer seals with its key to prove it holds it
+pub fn generate_challenge() -> String {
+    STANDARD_NO_PAD.encode(SalsaBox::generate_nonce(&mut OsRng))
+}
+
 pub fn encrypt(
     store: &KeyStore,
     client_public_key_b64: &str,
@@ -2,7 +2,6 @@ use std::sync::atomic::Ordering;
 
 use crate::errors::{error, info, warn, trace};
 use crate::errors::{Error, ErrorKind};
-use crate::config::Config;
 use crate::clipboard;
 use crate::transport::{Transport};
 use crate::utils;
@@ -10,7 +9,6 @@ use crate::utils;
 use super::constants::CLIPBOARD_TTL;
 
 pub(crate) async fn run_clipboard_source(
-    config: Config,
     transport: Transport,
 ) {
     info!("Starting clipboard source");
@@ -54,8 +52,13 @@ pub(crate) async fn run_clipboard_source(
 
         trace!("Clipboard changed, sending to peers");
 
-        for peer in &config.peers {
+        for peer in &transport.peers() {
             if peer.clipboard_destination {
+                if transport.is_peer_down(&peer.public_key) {
+                    trace!("Skipping clipboard send to down peer {}", peer.address);
+                    continue;
+                }
+
                 if !transport.send_clipboard(
                     peer.address.clone(),
                     peer.public_key.clone(),
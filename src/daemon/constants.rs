@@ -1 +1,8 @@
 pub(crate) const CLIPBOARD_TTL: u64 = 1000;
+pub(crate) const RELOAD_DEBOUNCE: u64 = 250;
+pub(crate) const WATCH_POLL_TIMEOUT: libc::c_int = 100;
+pub(crate) const HOTPLUG_DEBOUNCE: u64 = 500;
+pub(crate) const FOCUS_POLL_TIMEOUT: libc::c_int = 100;
+pub(crate) const INPUT_DEVICE_DIR: &str = "/dev/input";
+pub(crate) const GESTURE_ZOOM_STEP: f64 = 1.1;
+pub(crate) const POINTER_RETURN_THRESHOLD: i64 = 50;
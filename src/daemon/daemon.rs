@@ -1,18 +1,152 @@
 use std::sync::Arc;
 
+use tokio::sync::mpsc;
 use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
 
-use crate::errors::{error, info, trace};
+use crate::errors::{error, info, warn, trace};
 use crate::errors::{Result, Error, ErrorKind};
-use crate::config::Config;
+use crate::config::{Config, PeerConfig};
 use crate::crypto::KeyStore;
-use crate::transport::{Transport, send_active_state};
+use crate::metrics;
+use crate::synq::ActiveEvent;
+use crate::transport::Transport;
 use crate::utils;
 
+use super::constants::{RELOAD_DEBOUNCE, HOTPLUG_DEBOUNCE};
 use super::monitor::run_scroll_source_monitor;
-use super::scroll::run_scroll_blockers;
+use super::reload::{watch_config, restart_required};
+use super::hotplug::watch_input_devices;
+use super::focus::run_focus_activation;
+use super::scroll::ScrollBlockers;
 use super::clipboard::run_clipboard_source;
 
+struct Services {
+    config: Config,
+    transport: Transport,
+    scroll_source: Option<CancellationToken>,
+    scroll_blockers: Option<ScrollBlockers>,
+}
+
+impl Services {
+    async fn reload(&mut self) {
+        let new_config = match Config::load(self.config.path()).await {
+            Ok(config) => config,
+            Err(e) => {
+                let e = Error::wrap(e, ErrorKind::Read)
+                    .with_msg("daemon: Failed to reload configuration");
+                error(&e);
+                return;
+            }
+        };
+
+        if restart_required(&self.config.server, &new_config.server) {
+            warn!("Server options changed, restart required to apply");
+        }
+
+        let peers_changed = self.config.peers != new_config.peers;
+        let devices_changed = self.config.server.scroll_input_devices
+            != new_config.server.scroll_input_devices;
+        let mirror_changed = self.config.server.scroll_mirror != new_config.server.scroll_mirror;
+
+        if !peers_changed && !devices_changed && !mirror_changed {
+            trace!("Configuration reloaded, no changes");
+            return;
+        }
+
+        let mut config = self.config.clone();
+        config.peers = new_config.peers;
+        config.server.scroll_input_devices = new_config.server.scroll_input_devices;
+        config.server.scroll_mirror = new_config.server.scroll_mirror;
+
+        if peers_changed {
+            info!("Peers changed, updating connections");
+
+            self.transport.update_config(&config).await;
+
+            if config.server.scroll_source {
+                let added: Vec<_> = config.peers.iter()
+                    .filter(|p| !self.config.peers.iter().any(|c| {
+                        c.address == p.address && c.scroll_destination
+                    }))
+                    .cloned()
+                    .collect();
+                send_state_reset(&self.transport, &added, &config.server.public_key);
+            }
+        } else if mirror_changed {
+            self.transport.update_config(&config).await;
+        }
+
+        if devices_changed {
+            info!("Scroll input devices changed, restarting device threads");
+
+            if let Some(cancel) = self.scroll_source.take() {
+                cancel.cancel();
+                self.scroll_source = Some(
+                    start_scroll_source(&config, &self.transport));
+            }
+
+            if let Some(blockers) = &mut self.scroll_blockers &&
+                let Err(e) = blockers.update(&config.server.scroll_input_devices) {
+
+                error(&e);
+            }
+        }
+
+        self.config = config;
+    }
+
+    fn rescan_devices(&mut self) {
+        if let Some(blockers) = &mut self.scroll_blockers &&
+            let Err(e) = blockers.update(&self.config.server.scroll_input_devices) {
+
+            error(&e);
+        }
+    }
+}
+
+fn send_state_reset(transport: &Transport, peers: &[PeerConfig], host_key: &str) {
+    for peer in peers {
+        if peer.scroll_destination {
+            tokio::spawn({
+                let delivery = transport.active_delivery().clone();
+                let peer = peer.clone();
+                let event = ActiveEvent {
+                    peer: host_key.to_string(),
+                    clock: 0,
+                    epoch: transport.active_state().get_epoch(),
+                    source: host_key.to_string(),
+                    ack_id: 0,
+                };
+
+                async move {
+                    trace!(
+                        peer = %peer.address,
+                        "Send state reset",
+                    );
+                    delivery.deliver(&peer, event).await;
+                }
+            });
+        }
+    }
+}
+
+fn start_scroll_source(config: &Config, transport: &Transport) -> CancellationToken {
+    let cancel = transport.cancel_token().child_token();
+
+    tokio::task::spawn_blocking({
+        let config = config.clone();
+        let transport = transport.clone();
+        let cancel = cancel.clone();
+
+        move || {
+            run_scroll_source_monitor(config, transport, cancel);
+        }
+    });
+
+    cancel
+}
+
 pub async fn run(config: Config) -> Result<()> {
     let should_run_server = config.server.clipboard_destination
         || config.server.scroll_destination;
@@ -38,48 +172,73 @@ pub async fn run(config: Config) -> Result<()> {
 
     let transport = Transport::new(&config, key_store).await?;
 
-    if should_run_scroll_source {
-        let host_key = config.server.public_key.clone();
-        for peer in &config.peers {
-            if peer.scroll_destination {
-                tokio::spawn({
-                    let address = peer.address.clone();
-                    let host_key = host_key.clone();
-
-                    async move {
-                        trace!(
-                            peer = %address,
-                            "Send state reset",
-                        );
-                        if let Err(e) = send_active_state(&address, &host_key, 0).await {
-                            error(&e);
-                        }
-                    }
-                });
-            }
-        }
-
-        tokio::task::spawn_blocking({
-            let config = config.clone();
-            let transport = transport.clone();
+    if let Some(bind) = config.server.metrics_bind.clone() {
+        tokio::spawn({
+            let cancel = transport.cancel_token();
 
-            move || {
-                run_scroll_source_monitor(config, transport);
+            async move {
+                if let Err(e) = metrics::serve(bind, cancel).await {
+                    error(&e);
+                }
             }
         });
     }
 
+    let mut services = Services {
+        config: config.clone(),
+        transport: transport.clone(),
+        scroll_source: None,
+        scroll_blockers: None,
+    };
+
+    if should_run_scroll_source {
+        send_state_reset(&transport, &config.peers, &config.server.public_key);
+        services.scroll_source = Some(start_scroll_source(&config, &transport));
+
+        if config.server.focus_activation {
+            tokio::task::spawn_blocking({
+                let transport = transport.clone();
+                let cancel = transport.cancel_token();
+
+                move || {
+                    run_focus_activation(transport, cancel);
+                }
+            });
+        }
+    }
+
     if config.server.scroll_destination {
-        run_scroll_blockers(&config, transport.clone())?;
+        services.scroll_blockers = Some(
+            ScrollBlockers::start(&config, transport.clone())?);
     }
 
     if should_run_clipboard_source {
         tokio::spawn({
-            let config = config.clone();
             let transport = transport.clone();
 
             async move {
-                run_clipboard_source(config, transport).await;
+                run_clipboard_source(transport).await;
+            }
+        });
+    }
+
+    let (reload_tx, mut reload_rx) = mpsc::channel::<()>(1);
+    tokio::task::spawn_blocking({
+        let path = config.path().to_path_buf();
+        let cancel = transport.cancel_token();
+
+        move || {
+            watch_config(path, reload_tx, cancel);
+        }
+    });
+
+    let (hotplug_tx, mut hotplug_rx) = mpsc::channel::<()>(1);
+    if config.server.scroll_destination {
+        tokio::task::spawn_blocking({
+            let cancel = transport.cancel_token();
+
+            move || {
+                watch_input_devices(hotplug_tx, cancel);
             }
         });
     }
@@ -89,13 +248,39 @@ pub async fn run(config: Config) -> Result<()> {
     ).map_err(|e| Error::wrap(e, ErrorKind::Read)
         .with_msg("daemon: Failed to register SIGTERM handler"))?;
 
-    tokio::select! {
-        _ = tokio::signal::ctrl_c() => {
-            info!("Received SIGINT, shutting down");
-        }
-        _ = sigterm.recv() => {
-            info!("Received SIGTERM, shutting down");
+    let mut sighup = tokio::signal::unix::signal(
+        tokio::signal::unix::SignalKind::hangup(),
+    ).map_err(|e| Error::wrap(e, ErrorKind::Read)
+        .with_msg("daemon: Failed to register SIGHUP handler"))?;
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                info!("Received SIGINT, shutting down");
+                break;
+            }
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM, shutting down");
+                break;
+            }
+            _ = sighup.recv() => {
+                info!("Received SIGHUP, reloading configuration");
+            }
+            Some(()) = reload_rx.recv() => {
+                sleep(Duration::from_millis(RELOAD_DEBOUNCE)).await;
+                while reload_rx.try_recv().is_ok() {}
+                info!("Config file changed, reloading configuration");
+            }
+            Some(()) = hotplug_rx.recv() => {
+                sleep(Duration::from_millis(HOTPLUG_DEBOUNCE)).await;
+                while hotplug_rx.try_recv().is_ok() {}
+                trace!("Input devices changed, rescanning scroll devices");
+                services.rescan_devices();
+                continue;
+            }
         }
+
+        services.reload().await;
     }
 
     transport.shutdown();
@@ -0,0 +1,229 @@
+use std::os::unix::io::AsRawFd;
+
+use tokio_util::sync::CancellationToken;
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        xproto::{
+            Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask, Window,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+
+use crate::config::FocusWindow;
+use crate::errors::{error, info, trace};
+use crate::errors::{Error, ErrorKind, Result};
+use crate::scroll::PointerLocator;
+use crate::transport::Transport;
+use crate::utils::glob_match;
+
+use super::constants::FOCUS_POLL_TIMEOUT;
+
+struct FocusedWindow {
+    class: Vec<String>,
+    title: String,
+    pid: Option<u32>,
+}
+
+impl FocusedWindow {
+    fn matches(&self, focus: &FocusWindow) -> bool {
+        let mut matchers = [
+            focus.wm_class.as_ref().map(|c| self.class.iter().any(|wc| glob_match(c, wc))),
+            focus.title.as_ref().map(|t| glob_match(t, &self.title)),
+            focus.pid.map(|p| self.pid == Some(p)),
+        ].into_iter().flatten().peekable();
+
+        matchers.peek().is_some() && matchers.all(|m| m)
+    }
+}
+
+struct FocusWatcher {
+    conn: RustConnection,
+    root: Window,
+    active_window: Atom,
+    wm_name: Atom,
+    wm_pid: Atom,
+    utf8_string: Atom,
+}
+
+impl FocusWatcher {
+    fn new() -> Result<Self> {
+        let (conn, screen_num) = RustConnection::connect(None)
+            .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                .with_msg("daemon: Failed to connect to X11 display for focus activation"))?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let intern = |name: &[u8]| -> Result<Atom> {
+            conn.intern_atom(false, name)
+                .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                    .with_msg("daemon: Failed to intern X11 atom"))?
+                .reply()
+                .map(|r| r.atom)
+                .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                    .with_msg("daemon: Failed to intern X11 atom"))
+        };
+        let active_window = intern(b"_NET_ACTIVE_WINDOW")?;
+        let wm_name = intern(b"_NET_WM_NAME")?;
+        let wm_pid = intern(b"_NET_WM_PID")?;
+        let utf8_string = intern(b"UTF8_STRING")?;
+
+        conn.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )
+        .map_err(|e| Error::wrap(e, ErrorKind::Network)
+            .with_msg("daemon: Failed to watch root window properties"))?;
+        conn.flush()
+            .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                .with_msg("daemon: Failed to flush X11 connection"))?;
+
+        Ok(Self {
+            conn,
+            root,
+            active_window,
+            wm_name,
+            wm_pid,
+            utf8_string,
+        })
+    }
+
+    fn property(&self, window: Window, property: Atom, type_: impl Into<Atom>) -> Option<Vec<u8>> {
+        let reply = self.conn
+            .get_property(false, window, property, type_, 0, u32::MAX / 4)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(reply.value)
+    }
+
+    fn focused(&self) -> Option<FocusedWindow> {
+        let value = self.property(self.root, self.active_window, AtomEnum::WINDOW)?;
+        let window = u32::from_ne_bytes(value.get(..4)?.try_into().ok()?);
+        if window == 0 {
+            return None;
+        }
+
+        let class = self.property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING)
+            .unwrap_or_default()
+            .split(|b| *b == 0)
+            .filter(|s| !s.is_empty())
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+            .collect();
+
+        let title = self.property(window, self.wm_name, self.utf8_string)
+            .filter(|t| !t.is_empty())
+            .or_else(|| self.property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING))
+            .map(|t| String::from_utf8_lossy(&t).into_owned())
+            .unwrap_or_default();
+
+        let pid = self.property(window, self.wm_pid, AtomEnum::CARDINAL)
+            .and_then(|v| Some(u32::from_ne_bytes(v.get(..4)?.try_into().ok()?)));
+
+        Some(FocusedWindow {
+            class,
+            title,
+            pid,
+        })
+    }
+
+    fn wait_for_change(&self, cancel: &CancellationToken) -> Result<bool> {
+        let mut pfd = libc::pollfd {
+            fd: self.conn.stream().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        while !cancel.is_cancelled() {
+            let mut changed = false;
+            while let Some(event) = self.conn.poll_for_event()
+                .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                    .with_msg("daemon: Failed to read X11 event"))?
+            {
+                if let Event::PropertyNotify(notify) = event
+                    && notify.atom == self.active_window
+                {
+                    changed = true;
+                }
+            }
+            if changed {
+                return Ok(true);
+            }
+
+            let ret = unsafe { libc::poll(&mut pfd, 1, FOCUS_POLL_TIMEOUT) };
+            if ret < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(Error::wrap(err, ErrorKind::Read)
+                    .with_msg("daemon: Poll failed in focus watcher"));
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+pub(crate) fn pointer_locator() -> Result<PointerLocator> {
+    let (conn, screen_num) = RustConnection::connect(None)
+        .map_err(|e| Error::wrap(e, ErrorKind::Network)
+            .with_msg("daemon: Failed to connect to X11 display for pointer position"))?;
+    let root = conn.setup().roots[screen_num].root;
+
+    Ok(Box::new(move || {
+        let reply = conn.query_pointer(root).ok()?.reply().ok()?;
+        Some((reply.root_x as i32, reply.root_y as i32))
+    }))
+}
+
+fn activate_focused(transport: &Transport, focused: Option<FocusedWindow>) {
+    let peer = focused.and_then(|window| {
+        trace!(
+            class = ?window.class,
+            title = %window.title,
+            pid = ?window.pid,
+            "Focused window changed",
+        );
+
+        transport.peers().into_iter()
+            .filter(|p| p.scroll_destination)
+            .find(|p| p.focus_window.as_ref().is_some_and(|f| window.matches(f)))
+            .map(|p| p.public_key)
+    });
+
+    let active = transport.active_state().get_active_peer();
+    let changed = match &peer {
+        Some(peer) => active.as_ref() != Some(peer),
+        None => !transport.active_state().is_host_active(),
+    };
+
+    if changed {
+        transport.activate_peer(peer);
+    }
+}
+
+pub(crate) fn run_focus_activation(transport: Transport, cancel: CancellationToken) {
+    let watcher = match FocusWatcher::new() {
+        Ok(w) => w,
+        Err(e) => {
+            error(&e);
+            return;
+        }
+    };
+    info!("Started focus activation");
+
+    activate_focused(&transport, watcher.focused());
+
+    loop {
+        match watcher.wait_for_change(&cancel) {
+            Ok(true) => activate_focused(&transport, watcher.focused()),
+            Ok(false) => return,
+            Err(e) => {
+                error(&e);
+                return;
+            }
+        }
+    }
+}
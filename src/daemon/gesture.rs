@@ -0,0 +1,206 @@
+use crate::errors::{error, info, trace};
+use crate::errors::{Error, ErrorKind, Result};
+use crate::config::{GestureAction, GestureActionKind, GestureDirection, GestureKind};
+use crate::scroll::{KeyChord, KeySender, PointerSender, SharedUinput, VirtualTouchpad, KEY_LEFTCTRL};
+use crate::transport::GestureInjectRx;
+use crate::synq::{
+    GestureEvent,
+    GestureKind as ProtoGestureKind,
+    GesturePhase as ProtoGesturePhase,
+};
+
+use super::constants::GESTURE_ZOOM_STEP;
+
+enum ActiveGesture {
+    Touchpad,
+    Zoom {
+        steps: i32,
+    },
+    Keys {
+        kind: GestureKind,
+        fingers: u32,
+        dx: f64,
+        dy: f64,
+        scale: f64,
+    },
+}
+
+struct GestureInjector {
+    actions: Vec<GestureAction>,
+    keys: KeySender,
+    pointer: PointerSender,
+    touchpad: Option<VirtualTouchpad>,
+    touchpad_failed: bool,
+    active: Option<ActiveGesture>,
+}
+
+impl GestureInjector {
+    fn candidates(&self, kind: GestureKind, fingers: u32) -> impl Iterator<Item = &GestureAction> {
+        self.actions.iter().filter(move |a| {
+            a.gesture == kind && a.fingers.is_none_or(|f| f == fingers)
+        })
+    }
+
+    fn touchpad(&mut self) -> Option<&mut VirtualTouchpad> {
+        if self.touchpad.is_none() && !self.touchpad_failed {
+            match VirtualTouchpad::new() {
+                Ok(touchpad) => {
+                    info!("Created virtual touchpad for gesture replay");
+                    self.touchpad = Some(touchpad);
+                }
+                Err(e) => {
+                    let e = Error::wrap(e, ErrorKind::Exec)
+                        .with_msg("daemon: Failed to create virtual touchpad");
+                    error(&e);
+                    self.touchpad_failed = true;
+                }
+            }
+        }
+
+        self.touchpad.as_mut()
+    }
+
+    fn begin(&mut self, kind: GestureKind, fingers: u32) -> Result<()> {
+        self.finish(false)?;
+
+        let zoom = self.candidates(kind, fingers)
+            .any(|a| a.action == GestureActionKind::Zoom);
+        let keys = self.candidates(kind, fingers).next().is_some();
+
+        self.active = if zoom {
+            self.keys.send(KEY_LEFTCTRL, 1)?;
+            Some(ActiveGesture::Zoom { steps: 0 })
+        } else if keys {
+            Some(ActiveGesture::Keys { kind, fingers, dx: 0.0, dy: 0.0, scale: 1.0 })
+        } else if let Some(touchpad) = self.touchpad() {
+            touchpad.begin(fingers)?;
+            Some(ActiveGesture::Touchpad)
+        } else {
+            None
+        };
+
+        Ok(())
+    }
+
+    fn update(&mut self, event: &GestureEvent) -> Result<()> {
+        match &mut self.active {
+            Some(ActiveGesture::Touchpad) => {
+                if let Some(touchpad) = self.touchpad.as_mut() {
+                    touchpad.update(event.dx, event.dy, event.scale, event.angle_delta)?;
+                }
+            }
+            Some(ActiveGesture::Zoom { steps }) if event.scale > 0.0 => {
+                let target = (event.scale.ln() / GESTURE_ZOOM_STEP.ln()).trunc() as i32;
+                self.pointer.wheel(target - *steps)?;
+                *steps = target;
+            }
+            Some(ActiveGesture::Keys { dx, dy, scale, .. }) => {
+                *dx += event.dx;
+                *dy += event.dy;
+                *scale = event.scale;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self, completed: bool) -> Result<()> {
+        match self.active.take() {
+            Some(ActiveGesture::Touchpad) => {
+                if let Some(touchpad) = self.touchpad.as_mut() {
+                    touchpad.end()?;
+                }
+            }
+            Some(ActiveGesture::Zoom { .. }) => {
+                self.keys.send(KEY_LEFTCTRL, 0)?;
+            }
+            Some(ActiveGesture::Keys { kind, fingers, dx, dy, scale }) if completed => {
+                let direction = gesture_direction(kind, dx, dy, scale);
+                let action = self.candidates(kind, fingers)
+                    .find(|a| a.direction.is_none() || a.direction == direction);
+
+                if let Some(keys) = action.and_then(|a| a.keys.as_deref()) {
+                    trace!(keys = keys, direction = ?direction, "Gesture mapped to keys");
+                    self.tap(&KeyChord::parse(keys)?)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn tap(&self, chord: &KeyChord) -> Result<()> {
+        let codes = chord.codes();
+        for code in &codes {
+            self.keys.send(*code, 1)?;
+        }
+        for code in codes.iter().rev() {
+            self.keys.send(*code, 0)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, event: GestureEvent) -> Result<()> {
+        let kind = match event.kind() {
+            ProtoGestureKind::Swipe => GestureKind::Swipe,
+            ProtoGestureKind::Pinch => GestureKind::Pinch,
+            ProtoGestureKind::Hold => GestureKind::Hold,
+        };
+
+        match event.phase() {
+            ProtoGesturePhase::Begin => self.begin(kind, event.fingers),
+            ProtoGesturePhase::Update => self.update(&event),
+            ProtoGesturePhase::End => self.finish(true),
+            ProtoGesturePhase::Cancel => self.finish(false),
+        }
+    }
+}
+
+fn gesture_direction(
+    kind: GestureKind,
+    dx: f64,
+    dy: f64,
+    scale: f64,
+) -> Option<GestureDirection> {
+    match kind {
+        GestureKind::Swipe if dx.abs() > dy.abs() => {
+            Some(if dx > 0.0 { GestureDirection::Right } else { GestureDirection::Left })
+        }
+        GestureKind::Swipe if dy != 0.0 => {
+            Some(if dy > 0.0 { GestureDirection::Down } else { GestureDirection::Up })
+        }
+        GestureKind::Pinch if scale > 1.0 => Some(GestureDirection::Out),
+        GestureKind::Pinch if scale < 1.0 => Some(GestureDirection::In),
+        _ => None,
+    }
+}
+
+pub(crate) fn run_gesture_inject(
+    rx: GestureInjectRx,
+    uinput: SharedUinput,
+    actions: Vec<GestureAction>,
+) {
+    let mut injector = GestureInjector {
+        actions,
+        keys: KeySender::new(uinput.clone()),
+        pointer: PointerSender::new(uinput),
+        touchpad: None,
+        touchpad_failed: false,
+        active: None,
+    };
+    info!("Started gesture sender");
+
+    while let Some(event) = rx.recv() {
+        if let Err(e) = injector.handle(event) {
+            let e = Error::wrap(e, ErrorKind::Exec)
+                .with_msg("daemon: Failed to send gesture event");
+            error(&e);
+        }
+    }
+
+    if let Err(e) = injector.finish(false) {
+        error(&e);
+    }
+}
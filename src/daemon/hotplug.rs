@@ -0,0 +1,23 @@
+use std::path::Path;
+
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::{error, info};
+
+use super::constants::INPUT_DEVICE_DIR;
+use super::inotify::watch_inotify;
+
+const INOTIFY_MASK: u32 = libc::IN_CREATE | libc::IN_DELETE | libc::IN_ATTRIB;
+
+pub(crate) fn watch_input_devices(
+    tx: mpsc::Sender<()>,
+    cancel: CancellationToken,
+) {
+    info!("Watching {} for scroll device changes", INPUT_DEVICE_DIR);
+
+    let dir = Path::new(INPUT_DEVICE_DIR);
+    if let Err(e) = watch_inotify(dir, INOTIFY_MASK, |name| name.starts_with(b"event"), tx, cancel) {
+        error(&e);
+    }
+}
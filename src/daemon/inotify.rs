@@ -0,0 +1,112 @@
+use std::ffi::CString;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::{Result, Error, ErrorKind};
+
+use super::constants::WATCH_POLL_TIMEOUT;
+
+const INOTIFY_HEADER_SIZE: usize = mem::size_of::<libc::inotify_event>();
+
+// Signals tx whenever an event in dir names an entry the filter accepts. A
+// full channel already has a signal pending, so only a closed one stops it.
+pub(crate) fn watch_inotify(
+    dir: &Path,
+    mask: u32,
+    filter: impl Fn(&[u8]) -> bool,
+    tx: mpsc::Sender<()>,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let dir_c = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| Error::wrap(e, ErrorKind::Parse)
+            .with_msg("daemon: Invalid directory for watcher")
+            .with_ctx("path", dir.display()))?;
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(Error::wrap(std::io::Error::last_os_error(), ErrorKind::Exec)
+            .with_msg("daemon: Failed to initialize inotify"));
+    }
+    let _guard = scopeguard::guard(fd, |fd| unsafe {
+        libc::close(fd);
+    });
+
+    if unsafe { libc::inotify_add_watch(fd, dir_c.as_ptr(), mask) } < 0 {
+        return Err(Error::wrap(std::io::Error::last_os_error(), ErrorKind::Exec)
+            .with_msg("daemon: Failed to watch directory")
+            .with_ctx("path", dir.display()));
+    }
+
+    let mut buf = [0u8; 4096];
+
+    while !cancel.is_cancelled() {
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        let ret = unsafe { libc::poll(&mut pfd, 1, WATCH_POLL_TIMEOUT) };
+        if ret < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(Error::wrap(err, ErrorKind::Read)
+                .with_msg("daemon: Poll failed in directory watcher")
+                .with_ctx("path", dir.display()));
+        }
+
+        if ret == 0 {
+            continue;
+        }
+
+        let len = unsafe {
+            libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len())
+        };
+        if len < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::WouldBlock
+                || err.kind() == std::io::ErrorKind::Interrupted
+            {
+                continue;
+            }
+            return Err(Error::wrap(err, ErrorKind::Read)
+                .with_msg("daemon: Failed to read inotify events"));
+        }
+
+        let len = len as usize;
+        let mut offset = 0;
+        let mut changed = false;
+
+        while offset + INOTIFY_HEADER_SIZE <= len {
+            let event = unsafe {
+                std::ptr::read_unaligned(
+                    buf[offset..].as_ptr() as *const libc::inotify_event)
+            };
+            let name_start = offset + INOTIFY_HEADER_SIZE;
+            let name_end = (name_start + event.len as usize).min(len);
+            let name = &buf[name_start..name_end];
+            let name = match name.iter().position(|&b| b == 0) {
+                Some(i) => &name[..i],
+                None => name,
+            };
+
+            if filter(name) {
+                changed = true;
+            }
+
+            offset = name_end;
+        }
+
+        if changed && let Err(mpsc::error::TrySendError::Closed(_)) = tx.try_send(()) {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::{error, info, warn};
+use crate::errors::{Error, ErrorKind, Result};
+use crate::scroll::{KeyChord, KeyboardCapture, KeySender, SharedUinput};
+use crate::transport::{Transport, KeyInjectRx};
+use crate::synq::KeyEvent;
+
+use super::pointer::PointerSwitch;
+
+#[derive(Clone)]
+pub(crate) struct KeyboardHotkeys {
+    pub(crate) panic: KeyChord,
+    pub(crate) cycle: Option<(KeyChord, PointerSwitch)>,
+    pub(crate) peers: Option<PeerHotkeys>,
+}
+
+#[derive(Clone)]
+pub(crate) struct PeerHotkeys {
+    transport: Transport,
+    switch: Option<PointerSwitch>,
+    // Digit 0 selects the host, 1 through 9 the destination peers in order
+    chords: Vec<KeyChord>,
+}
+
+impl PeerHotkeys {
+    pub(crate) fn new(
+        prefix: &str,
+        transport: Transport,
+        switch: Option<PointerSwitch>,
+    ) -> Result<Self> {
+        let chords = (0..=9)
+            .map(|digit| KeyChord::parse(&format!("{}+{}", prefix, digit)))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            transport,
+            switch,
+            chords,
+        })
+    }
+
+    pub(crate) fn handle(&self, pressed: &HashSet<u16>, key: u16) -> bool {
+        let Some(digit) = self.chords.iter().position(|c| c.matches(pressed, key)) else {
+            return false;
+        };
+
+        let peer = match digit {
+            0 => None,
+            n => {
+                let peer = self.transport.peers().into_iter()
+                    .filter(|p| p.scroll_destination)
+                    .nth(n - 1);
+                if peer.is_none() {
+                    warn!("No destination peer for hotkey {}", n);
+                    return true;
+                }
+                peer
+            }
+        };
+
+        if let Some(switch) = &self.switch {
+            switch.select(peer.as_ref(), None);
+        }
+        // The switch only activates the host when it was forwarding itself
+        if self.switch.is_none()
+            || (peer.is_none() && !self.transport.active_state().is_host_active())
+        {
+            self.transport.activate_peer(peer.map(|p| p.public_key));
+        }
+
+        true
+    }
+}
+
+struct ForwardedKeys {
+    peer: Option<String>,
+    pressed: HashSet<u16>,
+}
+
+impl ForwardedKeys {
+    fn release(&mut self, transport: &Transport) {
+        let Some(peer) = self.peer.take() else {
+            return;
+        };
+
+        for code in self.pressed.drain() {
+            transport.send_key(&peer, KeyEvent {
+                code: code as u32,
+                value: 0,
+                time_usec: 0,
+            });
+        }
+    }
+}
+
+pub(crate) fn run_keyboard_source(
+    device_path: String,
+    transport: Transport,
+    hotkeys: KeyboardHotkeys,
+    cancel: CancellationToken,
+) {
+    let mut capture = match KeyboardCapture::new(&device_path) {
+        Ok(c) => c,
+        Err(e) => {
+            let e = Error::wrap(e, ErrorKind::Exec)
+                .with_msg("daemon: Failed to create keyboard capture")
+                .with_ctx("device", &device_path);
+            error(&e);
+            return;
+        }
+    };
+    let mut forwarded = ForwardedKeys {
+        peer: None,
+        pressed: HashSet::new(),
+    };
+    info!("Started keyboard capture on {}", device_path);
+
+    while !cancel.is_cancelled() {
+        let target = transport.keyboard_target();
+        if target != forwarded.peer {
+            forwarded.release(&transport);
+        }
+
+        let grab = target.is_some() && (capture.is_grabbed() || !capture.keys_held());
+        if let Err(e) = capture.set_grab(grab) {
+            error(&e.with_ctx("device", &device_path));
+        }
+        forwarded.peer = target.filter(|_| capture.is_grabbed());
+
+        let keys = match capture.read_keys() {
+            Ok(keys) => keys,
+            Err(e) => {
+                let e = Error::wrap(e, ErrorKind::Read)
+                    .with_msg("daemon: Keyboard capture error")
+                    .with_ctx("device", &device_path);
+                error(&e);
+                break;
+            }
+        };
+
+        let Some(peer) = forwarded.peer.clone() else {
+            continue;
+        };
+
+        for key in keys {
+            if key.value == 1 && hotkeys.panic.matches(capture.pressed(), key.code) {
+                warn!("Keyboard panic hotkey pressed, releasing grab");
+                if let Err(e) = capture.set_grab(false) {
+                    error(&e.with_ctx("device", &device_path));
+                }
+                forwarded.release(&transport);
+                transport.activate_peer(None);
+                break;
+            }
+
+            if let Some((chord, switch)) = &hotkeys.cycle
+                && key.value == 1
+                && chord.matches(capture.pressed(), key.code)
+            {
+                switch.cycle();
+                break;
+            }
+
+            if let Some(peers) = &hotkeys.peers
+                && key.value == 1
+                && peers.handle(capture.pressed(), key.code)
+            {
+                break;
+            }
+
+            match key.value {
+                0 => forwarded.pressed.remove(&key.code),
+                _ => forwarded.pressed.insert(key.code),
+            };
+            transport.send_key(&peer, KeyEvent {
+                code: key.code as u32,
+                value: key.value as u32,
+                time_usec: key.time_usec,
+            });
+        }
+    }
+
+    forwarded.release(&transport);
+}
+
+pub(crate) fn run_key_inject(rx: KeyInjectRx, uinput: SharedUinput) {
+    let sender = KeySender::new(uinput);
+    info!("Started key sender");
+
+    while let Some(event) = rx.recv() {
+        let Ok(code) = u16::try_from(event.code) else {
+            continue;
+        };
+        if let Err(e) = sender.send(code, event.value as i32) {
+            let e = Error::wrap(e, ErrorKind::Exec)
+                .with_msg("daemon: Failed to send key event");
+            error(&e);
+        }
+    }
+}
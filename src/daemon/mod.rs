@@ -3,5 +3,12 @@ mod daemon;
 pub use daemon::*;
 mod monitor;
 mod scroll;
+mod pointer;
+mod keyboard;
+mod gesture;
 mod clipboard;
+mod reload;
+mod hotplug;
+mod inotify;
+mod focus;
 mod constants;
@@ -1,19 +1,23 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
 use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::io::{AsRawFd, OwnedFd};
 use std::path::Path;
 
 use input::{DeviceCapability, Libinput, LibinputInterface, Event as LibinputEvent};
-use input::event::{DeviceEvent, EventTrait};
+use input::event::{DeviceEvent, EventTrait, KeyboardEvent};
+use input::event::keyboard::{KeyState, KeyboardEventTrait};
 use libc::{O_ACCMODE, O_RDONLY, O_RDWR, O_WRONLY};
 use tokio_util::sync::CancellationToken;
 
-use crate::config::{Config, InputDevice};
-use crate::errors::{Error, ErrorKind, error, info};
+use crate::config::Config;
+use crate::errors::{Error, ErrorKind, error, info, warn};
+use crate::scroll::{Device, KeyChord, ScrollPipeline, KEYBOARD_PANIC_HOTKEY};
 use crate::transport::Transport;
 
 use super::scroll::run_scroll_source;
+use super::pointer::{PointerSwitch, run_pointer_source};
+use super::keyboard::{KeyboardHotkeys, PeerHotkeys, run_keyboard_source};
 
 struct Interface;
 
@@ -43,9 +47,20 @@ struct ActiveReceiver {
     cancel: CancellationToken,
 }
 
+fn parse_hotkey(hotkey: &str) -> Option<KeyChord> {
+    match KeyChord::parse(hotkey) {
+        Ok(chord) => Some(chord),
+        Err(e) => {
+            error(&e);
+            None
+        }
+    }
+}
+
 pub(crate) fn run_scroll_source_monitor(
     config: Config,
     transport: Transport,
+    cancel: CancellationToken,
 ) {
     let mut libinput = Libinput::new_with_udev(Interface);
     if libinput.udev_assign_seat("seat0").is_err() {
@@ -57,15 +72,40 @@ pub(crate) fn run_scroll_source_monitor(
 
     let fd = libinput.as_raw_fd();
     let mut active_receivers: HashMap<String, ActiveReceiver> = HashMap::new();
-    let cancel = transport.cancel_token();
+    let mut active_keyboards: HashMap<String, ActiveReceiver> = HashMap::new();
     let input_devices = config.server.scroll_input_devices.clone();
-
-    let matches_config = |name: &str| -> Option<&InputDevice> {
-        input_devices.iter().find(|d| {
-            d.name.as_ref().is_some_and(|n| n.eq_ignore_ascii_case(name))
-        })
+    let keyboard_devices = if config.server.keyboard_sharing {
+        config.server.keyboard_input_devices.clone()
+    } else {
+        Vec::new()
     };
 
+    let pointer_switch = config.server.pointer_sharing
+        .then(|| PointerSwitch::new(transport.clone()));
+    let pointer_hotkey = config.server.pointer_hotkey.as_deref()
+        .and_then(parse_hotkey);
+    let panic_hotkey = (config.server.pointer_sharing || config.server.keyboard_sharing)
+        .then(|| config.server.keyboard_panic_hotkey.as_deref()
+            .unwrap_or(KEYBOARD_PANIC_HOTKEY))
+        .and_then(parse_hotkey);
+    let peer_hotkeys = config.server.peer_hotkeys.as_deref()
+        .and_then(|prefix| {
+            match PeerHotkeys::new(prefix, transport.clone(), pointer_switch.clone()) {
+                Ok(hotkeys) => Some(hotkeys),
+                Err(e) => {
+                    error(&e);
+                    None
+                }
+            }
+        });
+    let keyboard_hotkeys = panic_hotkey.clone().map(|panic| KeyboardHotkeys {
+        panic,
+        cycle: pointer_hotkey.clone().zip(pointer_switch.clone()),
+        peers: peer_hotkeys.clone(),
+    });
+    let gesture_sharing = config.server.gesture_sharing;
+    let mut pressed_keys: HashSet<u16> = HashSet::new();
+
     info!("Started scroll source monitor");
 
     loop {
@@ -106,41 +146,86 @@ pub(crate) fn run_scroll_source_monitor(
             match event {
                 LibinputEvent::Device(DeviceEvent::Added(evt)) => {
                     let device = evt.device();
-                    let name = device.name().to_string();
-                    let path = format!("/dev/input/{}", device.sysname());
+                    let info = Device::from_libinput(&device);
+                    let name = info.name.clone();
+                    let path = info.path.clone();
+
+                    if device.has_capability(DeviceCapability::Keyboard)
+                        && let Some(hotkeys) = &keyboard_hotkeys
+                        && keyboard_devices.iter().any(|d| d.matches(&info))
+                        && !active_keyboards.contains_key(&path)
+                    {
+                        info!("Keyboard device connected: {} ({})", name, path);
+
+                        let cancel = cancel.child_token();
+
+                        std::thread::spawn({
+                            let path = path.clone();
+                            let transport = transport.clone();
+                            let hotkeys = hotkeys.clone();
+                            let cancel = cancel.clone();
+
+                            move || {
+                                run_keyboard_source(path, transport, hotkeys, cancel);
+                            }
+                        });
+
+                        active_keyboards.insert(path.clone(), ActiveReceiver {
+                            cancel,
+                        });
+                    }
 
                     if !device.has_capability(DeviceCapability::Pointer) {
                         continue;
                     }
 
-                    if let Some(config) = matches_config(&name) {
-                        if active_receivers.contains_key(&name) {
+                    if let Some(config) = input_devices.iter().find(|d| d.matches(&info)) {
+                        if active_receivers.contains_key(&path) {
                             continue;
                         }
 
                         info!("Scroll device connected: {} ({})", name, path);
 
-                        let cancel = CancellationToken::new();
+                        let cancel = cancel.child_token();
 
                         std::thread::spawn({
                             let path = path.clone();
                             let transport = transport.clone();
-                            let scroll_reverse = config.scroll_reverse;
-                            let scroll_modifier = config.scroll_modifier;
+                            let pipeline = ScrollPipeline::for_device(config);
                             let cancel = cancel.clone();
 
                             move || {
                                 run_scroll_source(
                                     path,
                                     transport,
-                                    scroll_reverse,
-                                    scroll_modifier,
+                                    pipeline,
+                                    gesture_sharing,
                                     cancel,
                                 );
                             }
                         });
 
-                        active_receivers.insert(name, ActiveReceiver {
+                        if let Some(switch) = &pointer_switch {
+                            std::thread::spawn({
+                                let path = path.clone();
+                                let transport = transport.clone();
+                                let switch = switch.clone();
+                                let pipeline = ScrollPipeline::for_device(config);
+                                let cancel = cancel.clone();
+
+                                move || {
+                                    run_pointer_source(
+                                        path,
+                                        transport,
+                                        switch,
+                                        pipeline,
+                                        cancel,
+                                    );
+                                }
+                            });
+                        }
+
+                        active_receivers.insert(path, ActiveReceiver {
                             cancel,
                         });
                     }
@@ -148,22 +233,54 @@ pub(crate) fn run_scroll_source_monitor(
                 LibinputEvent::Device(DeviceEvent::Removed(evt)) => {
                     let device = evt.device();
                     let name = device.name().to_string();
+                    let path = format!("/dev/input/{}", device.sysname());
+
+                    if let Some(receiver) = active_keyboards.remove(&path) {
+                        info!("Keyboard device disconnected: {} ({})", name, path);
+                        receiver.cancel.cancel();
+                    }
 
                     if !device.has_capability(DeviceCapability::Pointer) {
                         continue;
                     }
 
-                    if let Some(receiver) = active_receivers.remove(&name) {
-                        info!("Scroll device disconnected: {}", name);
+                    if let Some(receiver) = active_receivers.remove(&path) {
+                        info!("Scroll device disconnected: {} ({})", name, path);
                         receiver.cancel.cancel();
                     }
                 }
+                LibinputEvent::Keyboard(KeyboardEvent::Key(evt)) => {
+                    let Ok(key) = u16::try_from(evt.key()) else {
+                        continue;
+                    };
+
+                    match evt.key_state() {
+                        KeyState::Pressed => {
+                            pressed_keys.insert(key);
+                            if panic_hotkey.as_ref().is_some_and(|h| h.matches(&pressed_keys, key))
+                                && !transport.active_state().is_host_active()
+                            {
+                                warn!("Panic hotkey pressed, returning input to host");
+                                transport.activate_peer(None);
+                            } else if let (Some(switch), Some(hotkey)) = (&pointer_switch, &pointer_hotkey)
+                                && hotkey.matches(&pressed_keys, key)
+                            {
+                                switch.cycle();
+                            } else if let Some(hotkeys) = &peer_hotkeys {
+                                hotkeys.handle(&pressed_keys, key);
+                            }
+                        }
+                        KeyState::Released => {
+                            pressed_keys.remove(&key);
+                        }
+                    }
+                }
                 _ => {}
             }
         }
     }
 
-    for (_, receiver) in active_receivers {
+    for (_, receiver) in active_receivers.into_iter().chain(active_keyboards) {
         receiver.cancel.cancel();
     }
 }
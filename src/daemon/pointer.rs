@@ -0,0 +1,330 @@
+use std::sync::{Arc, Mutex};
+
+use tokio_util::sync::CancellationToken;
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        xproto::{ChangeWindowAttributesAux, ConnectionExt, EventMask, Window},
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+
+use crate::errors::{error, info, warn, trace};
+use crate::errors::{Error, ErrorKind, Result};
+use crate::config::{PeerConfig, ScreenEdge};
+use crate::scroll::{PointerCapture, PointerInput, PointerSender, ScrollPipeline, ScrollSource, SharedUinput, V120_PER_DELTA};
+use crate::transport::{Transport, PointerInjectRx};
+use crate::synq::{PointerEvent, ScrollEvent, ScrollSource as ProtoScrollSource};
+
+use super::constants::POINTER_RETURN_THRESHOLD;
+
+struct PointerTarget {
+    public_key: String,
+    edge: Option<ScreenEdge>,
+    offset: i64,
+}
+
+#[derive(Clone)]
+pub(crate) struct PointerSwitch {
+    transport: Transport,
+    target: Arc<Mutex<Option<PointerTarget>>>,
+}
+
+impl PointerSwitch {
+    pub(crate) fn new(transport: Transport) -> Self {
+        Self {
+            transport,
+            target: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub(crate) fn forwarding(&self) -> Option<String> {
+        let mut target = self.target.lock().unwrap();
+        let public_key = target.as_ref()?.public_key.clone();
+
+        if self.transport.active_state().get_active_peer().as_deref() != Some(&public_key) {
+            trace!(peer = %public_key, "Pointer target no longer active");
+            *target = None;
+            return None;
+        }
+
+        Some(public_key)
+    }
+
+    pub(crate) fn select(&self, peer: Option<&PeerConfig>, edge: Option<ScreenEdge>) {
+        let mut target = self.target.lock().unwrap();
+
+        match peer {
+            Some(peer) => {
+                info!("Pointer switched to {}", peer.address);
+                *target = Some(PointerTarget {
+                    public_key: peer.public_key.clone(),
+                    edge,
+                    offset: 0,
+                });
+                self.transport.activate_peer(Some(peer.public_key.clone()));
+            }
+            None => {
+                if target.take().is_some() {
+                    info!("Pointer switched to host");
+                    self.transport.activate_peer(None);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn cycle(&self) {
+        let peers: Vec<PeerConfig> = self.transport.peers().into_iter()
+            .filter(|p| p.scroll_destination)
+            .collect();
+
+        let current = self.forwarding();
+        let next = match current {
+            Some(current) => peers.iter()
+                .position(|p| p.public_key == current)
+                .and_then(|i| peers.get(i + 1)),
+            None => peers.first(),
+        };
+
+        self.select(next, None);
+    }
+
+    fn track_return(&self, dx: i32, dy: i32) -> bool {
+        let mut target = self.target.lock().unwrap();
+        let Some(target) = target.as_mut() else {
+            return false;
+        };
+
+        let delta = match target.edge {
+            Some(ScreenEdge::Right) => dx,
+            Some(ScreenEdge::Left) => -dx,
+            Some(ScreenEdge::Bottom) => dy,
+            Some(ScreenEdge::Top) => -dy,
+            None => return false,
+        };
+        target.offset += delta as i64;
+
+        // Small wobbles back across the edge are part of entering the peer,
+        // only a clear move back returns to the host
+        target.offset < -POINTER_RETURN_THRESHOLD
+    }
+}
+
+// The screen size is cached and only refreshed when the root window is
+// resized, so each motion event costs a single pointer query
+struct ScreenGeometry {
+    conn: Option<(RustConnection, Window)>,
+    width: i32,
+    height: i32,
+    failed: bool,
+}
+
+impl ScreenGeometry {
+    fn new() -> Self {
+        Self {
+            conn: None,
+            width: 0,
+            height: 0,
+            failed: false,
+        }
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        let (conn, screen_num) = RustConnection::connect(None)
+            .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                .with_msg("daemon: Failed to connect to X11 display for edge switching"))?;
+        let root = conn.setup().roots[screen_num].root;
+
+        conn.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )
+        .map_err(|e| Error::wrap(e, ErrorKind::Network)
+            .with_msg("daemon: Failed to watch root window size"))?;
+
+        let geometry = conn.get_geometry(root)
+            .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                .with_msg("daemon: Failed to query screen geometry"))?
+            .reply()
+            .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                .with_msg("daemon: Failed to query screen geometry"))?;
+
+        self.width = geometry.width as i32;
+        self.height = geometry.height as i32;
+        self.conn = Some((conn, root));
+        Ok(())
+    }
+
+    fn refresh(&mut self) {
+        let Some((conn, root)) = self.conn.as_ref() else {
+            return;
+        };
+
+        while let Ok(Some(event)) = conn.poll_for_event() {
+            if let Event::ConfigureNotify(notify) = event
+                && notify.window == *root
+            {
+                trace!(width = notify.width, height = notify.height, "Screen resized");
+                self.width = notify.width as i32;
+                self.height = notify.height as i32;
+            }
+        }
+    }
+
+    fn edge(&mut self, dx: i32, dy: i32) -> Option<ScreenEdge> {
+        if self.conn.is_none() && !self.failed
+            && let Err(e) = self.connect()
+        {
+            warn!("Pointer edge switching disabled: {}", e);
+            self.failed = true;
+        }
+        self.refresh();
+        let (conn, root) = self.conn.as_ref()?;
+
+        let pointer = conn.query_pointer(*root).ok()?.reply().ok()?;
+
+        let x = pointer.root_x as i32;
+        let y = pointer.root_y as i32;
+
+        if dx > 0 && x >= self.width - 1 {
+            Some(ScreenEdge::Right)
+        } else if dx < 0 && x <= 0 {
+            Some(ScreenEdge::Left)
+        } else if dy > 0 && y >= self.height - 1 {
+            Some(ScreenEdge::Bottom)
+        } else if dy < 0 && y <= 0 {
+            Some(ScreenEdge::Top)
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) fn run_pointer_source(
+    device_path: String,
+    transport: Transport,
+    switch: PointerSwitch,
+    pipeline: ScrollPipeline,
+    cancel: CancellationToken,
+) {
+    let mut capture = match PointerCapture::new(&device_path) {
+        Ok(c) => c,
+        Err(e) => {
+            let e = Error::wrap(e, ErrorKind::Exec)
+                .with_msg("daemon: Failed to create pointer capture")
+                .with_ctx("device", &device_path);
+            error(&e);
+            return;
+        }
+    };
+    let mut screen = ScreenGeometry::new();
+    info!("Started pointer capture on {}", device_path);
+
+    while !cancel.is_cancelled() {
+        let forwarding = switch.forwarding().is_some();
+        if let Err(e) = capture.set_grab(forwarding) {
+            error(&e.with_ctx("device", &device_path));
+        }
+
+        let inputs = match capture.read_inputs() {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                let e = Error::wrap(e, ErrorKind::Read)
+                    .with_msg("daemon: Pointer capture error")
+                    .with_ctx("device", &device_path);
+                error(&e);
+                return;
+            }
+        };
+
+        for input in inputs {
+            if !capture.is_grabbed() {
+                if let PointerInput::Motion { dx, dy, .. } = input {
+                    switch_on_edge(&transport, &switch, &mut screen, dx, dy);
+                }
+                continue;
+            }
+
+            match input {
+                PointerInput::Motion { dx, dy, time_usec } => {
+                    if switch.track_return(dx, dy) {
+                        switch.select(None, None);
+                        break;
+                    }
+                    transport.send_pointer(PointerEvent {
+                        dx,
+                        dy,
+                        button: 0,
+                        pressed: false,
+                        time_usec,
+                    });
+                }
+                PointerInput::Button { code, pressed, time_usec } => {
+                    transport.send_pointer(PointerEvent {
+                        dx: 0,
+                        dy: 0,
+                        button: code as u32,
+                        pressed,
+                        time_usec,
+                    });
+                }
+                PointerInput::Scroll { v120_x, v120_y, time_usec } => {
+                    let (delta_x, delta_y) = pipeline.apply(
+                        ScrollSource::Wheel,
+                        v120_x as f64 / V120_PER_DELTA,
+                        v120_y as f64 / V120_PER_DELTA,
+                    );
+                    transport.send_scroll(ScrollEvent {
+                        source: ProtoScrollSource::Wheel.into(),
+                        delta_x,
+                        delta_y,
+                        time_usec,
+                        stop: false,
+                        mirror: false,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn switch_on_edge(
+    transport: &Transport,
+    switch: &PointerSwitch,
+    screen: &mut ScreenGeometry,
+    dx: i32,
+    dy: i32,
+) {
+    let peers = transport.peers();
+    if !peers.iter().any(|p| p.scroll_destination && p.pointer_edge.is_some()) {
+        return;
+    }
+
+    let Some(edge) = screen.edge(dx, dy) else {
+        return;
+    };
+
+    let peer = peers.iter()
+        .find(|p| p.scroll_destination && p.pointer_edge == Some(edge));
+    if let Some(peer) = peer {
+        if transport.is_peer_down(&peer.public_key) {
+            return;
+        }
+        switch.select(Some(peer), Some(edge));
+    }
+}
+
+pub(crate) fn run_pointer_inject(rx: PointerInjectRx, uinput: SharedUinput) {
+    let sender = PointerSender::new(uinput);
+    info!("Started pointer sender");
+
+    while let Some(event) = rx.recv() {
+        let button = u16::try_from(event.button).unwrap_or_default();
+        if let Err(e) = sender.send(event.dx, event.dy, button, event.pressed) {
+            let e = Error::wrap(e, ErrorKind::Exec)
+                .with_msg("daemon: Failed to send pointer event");
+            error(&e);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::{error, info};
+use crate::errors::{Error, ErrorKind};
+use crate::config::{ServerConfig};
+
+use super::inotify::watch_inotify;
+
+const INOTIFY_MASK: u32 = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE;
+
+// Only the options reload applies are left out, any other change, including
+// options added later, needs a restart
+pub(crate) fn restart_required(current: &ServerConfig, new: &ServerConfig) -> bool {
+    let mut new = new.clone();
+    new.scroll_input_devices = current.scroll_input_devices.clone();
+    new.scroll_mirror = current.scroll_mirror;
+
+    *current != new
+}
+
+pub(crate) fn watch_config(
+    path: PathBuf,
+    tx: mpsc::Sender<()>,
+    cancel: CancellationToken,
+) {
+    let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+        let e = Error::new(ErrorKind::Invalid)
+            .with_msg("daemon: Invalid config path for watcher")
+            .with_ctx("path", path.display());
+        error(&e);
+        return;
+    };
+
+    info!("Watching config file {}", path.display());
+
+    let filter = |name: &[u8]| name == file_name.as_bytes();
+    if let Err(e) = watch_inotify(dir, INOTIFY_MASK, filter, tx, cancel) {
+        error(&e);
+    }
+}
@@ -1,17 +1,38 @@
+use std::collections::HashMap;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
 use tokio_util::sync::CancellationToken;
 
 use crate::errors::{error, info, trace};
 use crate::errors::{Error, ErrorKind, Result};
-use crate::config::Config;
-use crate::scroll::{ScrollReceiver, ScrollBlocker, ScrollSender, ScrollSource, SharedUinput, resolve_devices};
+use crate::config::{Config, InputDevice, GestureKind, KineticScroll, ScrollOutput};
+use crate::config::{ActivationTrigger, ScrollActivation};
+use crate::scroll::{ScrollReceiver, ScrollBlocker, ScrollSender, ScrollSource, SharedUinput, ResolvedDevice, resolve_devices};
+use crate::scroll::UinputCapabilities;
+use crate::scroll::{ActivationFilter, JitterBuffer, KineticScroller, ScrollPipeline, JITTER_LATENCY_BUDGET};
+use crate::scroll::scroll_source;
+use crate::scroll::{ReceiverEvent, GestureInput, GesturePhase};
+use crate::metrics::METRICS;
+use crate::utils;
 use crate::transport::{Transport, ScrollInjectRx, ActiveState};
 use crate::synq::{ScrollEvent, ScrollSource as ProtoScrollSource};
+use crate::synq::{
+    GestureEvent,
+    GestureKind as ProtoGestureKind,
+    GesturePhase as ProtoGesturePhase,
+};
+
+use super::focus::pointer_locator;
+use super::pointer::run_pointer_inject;
+use super::keyboard::run_key_inject;
+use super::gesture::run_gesture_inject;
 
 pub(crate) fn run_scroll_source(
     device_path: String,
     transport: Transport,
-    scroll_reverse: bool,
-    scroll_modifier: f64,
+    pipeline: ScrollPipeline,
+    gesture_sharing: bool,
     cancel: CancellationToken,
 ) {
     let mut receiver = match ScrollReceiver::new(&device_path) {
@@ -28,12 +49,16 @@ pub(crate) fn run_scroll_source(
 
     while !cancel.is_cancelled() {
         match receiver.read_event() {
-            Ok(Some(event)) => {
-                let (delta_x, delta_y) = if scroll_reverse {
-                    (-event.delta_x * scroll_modifier, -event.delta_y * scroll_modifier)
-                } else {
-                    (event.delta_x * scroll_modifier, event.delta_y * scroll_modifier)
-                };
+            Ok(Some(ReceiverEvent::Gesture(gesture))) => {
+                if gesture_sharing {
+                    transport.send_gesture(proto_gesture(gesture));
+                }
+            }
+            Ok(Some(ReceiverEvent::Scroll(event))) => {
+                let (delta_x, delta_y) = pipeline.apply(event.source, event.delta_x, event.delta_y);
+                if delta_x == 0.0 && delta_y == 0.0 && !event.stop {
+                    continue;
+                }
 
                 trace!(
                     source = ?event.source,
@@ -52,9 +77,12 @@ pub(crate) fn run_scroll_source(
                     source: proto_source.into(),
                     delta_x,
                     delta_y,
+                    time_usec: event.time_usec,
+                    stop: event.stop,
+                    mirror: false,
                 };
 
-                let _ = transport.send_scroll(scroll_event);
+                transport.send_scroll(scroll_event);
             }
             Ok(None) => {}
             Err(e) => {
@@ -68,19 +96,105 @@ pub(crate) fn run_scroll_source(
     }
 }
 
+fn proto_gesture(gesture: GestureInput) -> GestureEvent {
+    let kind = match gesture.kind {
+        GestureKind::Swipe => ProtoGestureKind::Swipe,
+        GestureKind::Pinch => ProtoGestureKind::Pinch,
+        GestureKind::Hold => ProtoGestureKind::Hold,
+    };
+    let phase = match gesture.phase {
+        GesturePhase::Begin => ProtoGesturePhase::Begin,
+        GesturePhase::Update => ProtoGesturePhase::Update,
+        GesturePhase::End => ProtoGesturePhase::End,
+        GesturePhase::Cancel => ProtoGesturePhase::Cancel,
+    };
+
+    GestureEvent {
+        kind: kind.into(),
+        phase: phase.into(),
+        fingers: gesture.fingers,
+        dx: gesture.dx,
+        dy: gesture.dy,
+        scale: gesture.scale,
+        angle_delta: gesture.angle_delta,
+        time_usec: gesture.time_usec,
+    }
+}
+
 pub(crate) fn run_scroll_inject(
     rx: ScrollInjectRx,
     uinput: SharedUinput,
     transport: Transport,
+    latency_budget: u64,
+    kinetic: Option<KineticScroll>,
+    pipeline: ScrollPipeline,
+    output: ScrollOutput,
 ) {
-    let mut sender = ScrollSender::new(uinput, transport);
+    let mut sender = ScrollSender::new(uinput, transport, pipeline, output);
+    let mut jitter = JitterBuffer::new(latency_budget);
+    let mut kinetic = kinetic.map(|k| KineticScroller::new(k.decay, k.min_velocity));
     info!("Started scroll sender");
 
-    while let Some(event) = rx.recv() {
-        if let Err(e) = sender.send(event.delta_x, event.delta_y) {
-            let e = Error::wrap(e, ErrorKind::Exec)
-                .with_msg("daemon: Failed to send scroll event");
-            error(&e);
+    loop {
+        let deadline = [
+            jitter.next_deadline(),
+            kinetic.as_ref().and_then(|k| k.next_deadline()),
+        ].into_iter().flatten().min();
+
+        let received = match deadline {
+            Some(deadline) => {
+                let wait = deadline.saturating_sub(utils::mono_time_us());
+                match rx.recv_timeout(Duration::from_micros(wait)) {
+                    Ok(event) => Some(event),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            None => match rx.recv() {
+                Some(event) => Some(event),
+                None => return,
+            },
+        };
+
+        if let Some(event) = received {
+            let source = event.source().as_str_name();
+            if !jitter.push(event, utils::mono_time_us()) {
+                METRICS.scroll_late.inc(source);
+            }
+        }
+
+        while let Some(mut event) = jitter.pop_due(utils::mono_time_us()) {
+            let (delta_x, delta_y) = sender.transform(scroll_source(&event), event.delta_x, event.delta_y);
+            event.delta_x = delta_x;
+            event.delta_y = delta_y;
+
+            match sender.send_transformed(delta_x, delta_y, event.mirror) {
+                Ok(true) => {
+                    if let Some(kinetic) = kinetic.as_mut() {
+                        kinetic.track(&event, utils::mono_time_us());
+                    }
+                }
+                Ok(false) => {
+                    if let Some(kinetic) = kinetic.as_mut() {
+                        kinetic.cancel();
+                    }
+                }
+                Err(e) => {
+                    let e = Error::wrap(e, ErrorKind::Exec)
+                        .with_msg("daemon: Failed to send scroll event");
+                    error(&e);
+                }
+            }
+        }
+
+        while let Some((dx, dy)) = kinetic.as_mut()
+            .and_then(|k| k.pop_due(utils::mono_time_us()))
+        {
+            if let Err(e) = sender.send_synthesized(dx, dy) {
+                let e = Error::wrap(e, ErrorKind::Exec)
+                    .with_msg("daemon: Failed to send kinetic scroll event");
+                error(&e);
+            }
         }
     }
 }
@@ -89,10 +203,22 @@ pub(crate) fn run_scroll_blocker(
     device_path: String,
     uinput: SharedUinput,
     active_state: ActiveState,
+    activation: ScrollActivation,
     transport: Transport,
     cancel: CancellationToken,
 ) {
-    let on_scroll: Box<dyn Fn() + Send> = Box::new(move || {
+    let locator = match activation.trigger {
+        ActivationTrigger::Region => match pointer_locator() {
+            Ok(locator) => Some(locator),
+            Err(e) => {
+                error(&e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    let on_activate: Box<dyn Fn() + Send> = Box::new(move || {
         transport.send_activate_request();
     });
 
@@ -100,7 +226,8 @@ pub(crate) fn run_scroll_blocker(
         &device_path,
         uinput,
         active_state,
-        Some(on_scroll),
+        ActivationFilter::new(activation, locator),
+        Some(on_activate),
     ) {
         Ok(b) => b,
         Err(e) => {
@@ -122,64 +249,213 @@ pub(crate) fn run_scroll_blocker(
     }
 }
 
-pub(crate) fn run_scroll_blockers(
-    config: &Config,
-    transport: Transport,
-) -> Result<()> {
-    let scroll_inject_rx = transport.take_scroll_inject_rx();
-
-    let blocker_devices = resolve_devices(
-        &config.server.scroll_input_devices)?;
-
-    let first_device_path = blocker_devices.first()
-        .map(|d| d.path.clone())
-        .ok_or_else(|| Error::new(ErrorKind::Invalid)
-            .with_msg("daemon: No scroll input devices configured"))?;
-
+// Only the target clone injects pointer and key input, the others just
+// mirror their source device
+fn create_uinput(
+    device: &ResolvedDevice,
+    target: Option<SharedUinput>,
+    capabilities: UinputCapabilities,
+) -> Result<SharedUinput> {
     let source_file = std::fs::OpenOptions::new()
         .read(true)
-        .open(&first_device_path)
+        .open(&device.path)
         .map_err(|e| Error::wrap(e, ErrorKind::Read)
             .with_msg("daemon: Failed to open scroll device for uinput setup")
-            .with_ctx("path", &first_device_path))?;
+            .with_ctx("path", &device.path))?;
     let source_fd = std::os::unix::io::AsRawFd::as_raw_fd(&source_file);
 
-    let shared_uinput = SharedUinput::new(source_fd)
-        .map_err(|e| Error::wrap(e, ErrorKind::Exec)
-            .with_msg("daemon: Failed to create shared uinput device"))?;
+    let uinput = match target {
+        Some(uinput) => {
+            info!("Injecting scroll through clone of {}", device.path);
+            uinput.rebuild(source_fd, &device.virtual_name, device.virtual_id, capabilities)
+                .map(|_| uinput)
+        }
+        None => SharedUinput::new(
+            source_fd,
+            &device.virtual_name,
+            device.virtual_id,
+            UinputCapabilities::default(),
+        ),
+    };
 
-    drop(source_file);
+    uinput.map_err(|e| Error::wrap(e, ErrorKind::Exec)
+        .with_msg("daemon: Failed to create uinput device")
+        .with_ctx("path", &device.path))
+}
 
-    if let Some(rx) = scroll_inject_rx {
-        tokio::task::spawn_blocking({
-            let shared_uinput = shared_uinput.clone();
-            let transport = transport.clone();
+pub(crate) struct ScrollBlockers {
+    transport: Transport,
+    target_path: Option<String>,
+    target_uinput: SharedUinput,
+    capabilities: UinputCapabilities,
+    blockers: HashMap<String, CancellationToken>,
+}
 
-            move || {
-                run_scroll_inject(rx, shared_uinput, transport);
-            }
-        });
+impl ScrollBlockers {
+    pub(crate) fn start(
+        config: &Config,
+        transport: Transport,
+    ) -> Result<Self> {
+        let scroll_inject_rx = transport.take_scroll_inject_rx();
+        let pointer_inject_rx = transport.take_pointer_inject_rx();
+        let key_inject_rx = transport.take_key_inject_rx();
+        let gesture_inject_rx = transport.take_gesture_inject_rx();
+
+        let shared_uinput = SharedUinput::detached();
+
+        if let Some(rx) = scroll_inject_rx {
+            tokio::task::spawn_blocking({
+                let shared_uinput = shared_uinput.clone();
+                let transport = transport.clone();
+                let latency_budget = config.server.scroll_latency_budget
+                    .unwrap_or(JITTER_LATENCY_BUDGET);
+                let kinetic = config.server.kinetic_scroll.clone();
+                let pipeline = ScrollPipeline::new(config.server.scroll_transform.clone());
+                let output = config.server.scroll_output.unwrap_or(ScrollOutput::Both);
+
+                move || {
+                    run_scroll_inject(
+                        rx,
+                        shared_uinput,
+                        transport,
+                        latency_budget,
+                        kinetic,
+                        pipeline,
+                        output,
+                    );
+                }
+            });
+        }
+
+        if let Some(rx) = pointer_inject_rx {
+            tokio::task::spawn_blocking({
+                let shared_uinput = shared_uinput.clone();
+
+                move || {
+                    run_pointer_inject(rx, shared_uinput);
+                }
+            });
+        }
+
+        if let Some(rx) = key_inject_rx {
+            tokio::task::spawn_blocking({
+                let shared_uinput = shared_uinput.clone();
+
+                move || {
+                    run_key_inject(rx, shared_uinput);
+                }
+            });
+        }
+
+        if let Some(rx) = gesture_inject_rx {
+            tokio::task::spawn_blocking({
+                let shared_uinput = shared_uinput.clone();
+                let actions = config.server.gesture_actions.clone();
+
+                move || {
+                    run_gesture_inject(rx, shared_uinput, actions);
+                }
+            });
+        }
+
+        let mut blockers = Self {
+            transport,
+            target_path: None,
+            target_uinput: shared_uinput,
+            capabilities: UinputCapabilities {
+                pointer: config.server.pointer_sharing,
+                keyboard: config.server.keyboard_sharing,
+            },
+            blockers: HashMap::new(),
+        };
+        blockers.update(&config.server.scroll_input_devices)?;
+
+        if blockers.blockers.is_empty() {
+            info!("No scroll input devices present, waiting for them to connect");
+        }
+
+        Ok(blockers)
     }
 
-    let cancel = transport.cancel_token();
-    for device in blocker_devices {
-        tokio::task::spawn_blocking({
-            let cancel = cancel.clone();
-            let active_state = transport.active_state().clone();
-            let transport = transport.clone();
-            let shared_uinput = shared_uinput.clone();
-
-            move || {
-                run_scroll_blocker(
-                    device.path,
-                    shared_uinput,
-                    active_state,
-                    transport,
-                    cancel,
-                );
+    pub(crate) fn update(&mut self, input_devices: &[InputDevice]) -> Result<()> {
+        let blocker_devices = resolve_devices(input_devices)?;
+
+        self.blockers.retain(|path, cancel| {
+            // Blockers mark their token cancelled when the device goes away
+            if cancel.is_cancelled() {
+                return false;
+            }
+
+            let keep = blocker_devices.iter().any(|d| d.path == *path);
+            if !keep {
+                info!("Stopping scroll blocker on {}", path);
+                cancel.cancel();
             }
+            keep
         });
+
+        let previous_target = self.target_path.clone();
+        if self.target_path.as_ref().is_some_and(|p| !self.blockers.contains_key(p)) {
+            self.target_path = None;
+        }
+        if self.target_path.is_none() {
+            self.target_path = blocker_devices.iter()
+                .find(|d| d.scroll_target)
+                .or_else(|| blocker_devices.first())
+                .map(|d| d.path.clone());
+        }
+
+        // A new target with a blocker already running is not started again,
+        // so the clone still mirrors the old target until it is rebuilt here
+        if self.target_path != previous_target
+            && let Some(path) = &self.target_path
+            && self.blockers.contains_key(path)
+            && let Some(device) = blocker_devices.iter().find(|d| d.path == *path)
+            && let Err(e) = create_uinput(device, Some(self.target_uinput.clone()), self.capabilities)
+        {
+            error(&e);
+        }
+
+        self.start_devices(blocker_devices);
+
+        Ok(())
     }
 
-    Ok(())
+    fn start_devices(&mut self, devices: Vec<ResolvedDevice>) {
+        for device in devices {
+            if self.blockers.contains_key(&device.path) {
+                continue;
+            }
+            let path = device.path.clone();
+
+            let cancel = self.transport.cancel_token().child_token();
+
+            let target_uinput = (self.target_path.as_ref() == Some(&device.path))
+                .then(|| self.target_uinput.clone());
+
+            tokio::task::spawn_blocking({
+                let cancel = cancel.clone();
+                let active_state = self.transport.active_state().clone();
+                let transport = self.transport.clone();
+                let capabilities = self.capabilities;
+
+                move || {
+                    match create_uinput(&device, target_uinput, capabilities) {
+                        Ok(shared_uinput) => run_scroll_blocker(
+                            device.path,
+                            shared_uinput,
+                            active_state,
+                            device.activation,
+                            transport,
+                            cancel.clone(),
+                        ),
+                        Err(e) => error(&e),
+                    }
+                    cancel.cancel();
+                }
+            });
+
+            self.blockers.insert(path, cancel);
+        }
+    }
 }
@@ -10,11 +10,17 @@ mod clipboard;
 mod synq;
 mod transport;
 mod broadcast;
+mod metrics;
+mod record;
+
+use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
-use crate::errors::Result;
+use crate::errors::{Result, Error, ErrorKind};
 use crate::config::Config;
 use crate::utils::get_config_path;
+use crate::scroll::{ScrollGenerator, ScrollPattern};
+use crate::scroll::{SCROLL_TEST_DURATION, SCROLL_TEST_INTERVAL, SCROLL_TEST_SPEED};
 
 #[derive(Parser, Debug)]
 #[command(name = "synq")]
@@ -34,9 +40,46 @@ enum Command {
         #[arg(long)]
         scroll: bool,
     },
-    ListDevices,
+    ListDevices {
+        #[arg(long, conflicts_with = "yaml")]
+        json: bool,
+        #[arg(long)]
+        yaml: bool,
+    },
     DetectDevices,
     GenerateKey,
+    Mirror {
+        #[arg(long, conflicts_with = "off")]
+        on: bool,
+        #[arg(long)]
+        off: bool,
+    },
+    Record {
+        file: PathBuf,
+        #[arg(long)]
+        device: Option<String>,
+    },
+    Replay {
+        file: PathBuf,
+        #[arg(long)]
+        peer: Option<String>,
+        #[arg(long)]
+        device: Option<String>,
+    },
+    ScrollTest {
+        #[arg(long, value_enum, default_value_t = ScrollPattern::Constant)]
+        pattern: ScrollPattern,
+        #[arg(long, default_value_t = SCROLL_TEST_DURATION)]
+        duration: u64,
+        #[arg(long, default_value_t = SCROLL_TEST_INTERVAL)]
+        interval: u64,
+        #[arg(long, default_value_t = SCROLL_TEST_SPEED)]
+        speed: f64,
+        #[arg(long)]
+        peer: Option<String>,
+        #[arg(long)]
+        device: Option<String>,
+    },
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -78,10 +121,36 @@ async fn main() -> Result<()> {
 
                 configure::configure(config, scroll).await?;
             }
-            Command::ListDevices => {
+            Command::ListDevices { json, yaml } => {
                 let devices = scroll::list_devices()?;
-                for device in devices {
-                    println!("{}", device);
+
+                if json || yaml {
+                    // Matching is only reported when there is a config,
+                    // a config that fails to load is still an error
+                    let config_path = get_config_path()?;
+                    let config = if config_path.exists() {
+                        Some(Config::load(&config_path).await?)
+                    } else {
+                        None
+                    };
+                    let reports: Vec<_> = devices.iter()
+                        .map(|d| d.report(config.as_ref()))
+                        .collect();
+
+                    let output = if json {
+                        serde_json::to_string_pretty(&reports)
+                            .map_err(|e| Error::wrap(e, ErrorKind::Write)
+                                .with_msg("main: Failed to serialize devices"))?
+                    } else {
+                        serde_saphyr::to_string(&reports)
+                            .map_err(|e| Error::wrap(e, ErrorKind::Write)
+                                .with_msg("main: Failed to serialize devices"))?
+                    };
+                    println!("{}", output);
+                } else {
+                    for device in devices {
+                        println!("{}", device);
+                    }
                 }
             }
             Command::DetectDevices => {
@@ -103,6 +172,45 @@ async fn main() -> Result<()> {
 
                 println!("{}", public_key);
             }
+            Command::Mirror { on, off } => {
+                let config_path = get_config_path()?;
+                let mut config = Config::load(&config_path).await?;
+
+                let enabled = if on || off {
+                    on
+                } else {
+                    !config.server.scroll_mirror
+                };
+                config.set_scroll_mirror(enabled);
+                config.save().await?;
+
+                println!("Scroll mirror {}", if enabled { "enabled" } else { "disabled" });
+            }
+            Command::Record { file, device } => {
+                let config_path = get_config_path()?;
+                let config = Config::load(&config_path).await?;
+
+                record::record(config, &file, device).await?;
+            }
+            Command::Replay { file, peer, device } => {
+                let config_path = get_config_path()?;
+                let config = Config::load(&config_path).await?;
+
+                record::replay(config, &file, peer, device).await?;
+            }
+            Command::ScrollTest { pattern, duration, interval, speed, peer, device } => {
+                if speed == 0.0 || !speed.is_finite() {
+                    return Err(Error::new(ErrorKind::Invalid)
+                        .with_msg("main: Scroll test speed must be a non-zero number")
+                        .with_ctx("speed", speed));
+                }
+
+                let config_path = get_config_path()?;
+                let config = Config::load(&config_path).await?;
+
+                let generator = ScrollGenerator::new(pattern, duration, interval, speed);
+                record::scroll_test(config, generator, peer, device).await?;
+            }
         }
     }
 
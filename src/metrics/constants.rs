@@ -0,0 +1,5 @@
+pub(crate) const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+pub(crate) const REQUEST_TIMEOUT: u64 = 5000;
+pub(crate) const REQUEST_MAX_SIZE: usize = 8192;
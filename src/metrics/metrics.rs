@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::LazyLock;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::constants::LATENCY_BUCKETS;
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    label: &'static str,
+    values: RwLock<BTreeMap<String, AtomicU64>>,
+}
+
+impl Counter {
+    fn new(name: &'static str, help: &'static str, label: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            label,
+            values: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn inc(&self, label: &str) {
+        self.inc_by(label, 1);
+    }
+
+    pub fn inc_by(&self, label: &str, value: u64) {
+        {
+            let values = self.values.read().unwrap();
+            if let Some(counter) = values.get(label) {
+                counter.fetch_add(value, Ordering::Relaxed);
+                return;
+            }
+        }
+
+        self.values.write().unwrap()
+            .entry(label.to_string())
+            .or_insert_with(|| AtomicU64::new(0))
+            .fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+
+        for (label, value) in self.values.read().unwrap().iter() {
+            let _ = writeln!(
+                out, "{}{{{}=\"{}\"}} {}",
+                self.name, self.label, escape_label(label),
+                value.load(Ordering::Relaxed),
+            );
+        }
+    }
+}
+
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Gauge {
+    fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set(&self, value: u64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} gauge", self.name);
+        let _ = writeln!(out, "{} {}", self.name, self.value.load(Ordering::Relaxed));
+    }
+}
+
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    buckets: &'static [f64],
+    counts: Vec<AtomicU64>,
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn new(
+        name: &'static str,
+        help: &'static str,
+        buckets: &'static [f64],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            buckets,
+            counts: buckets.iter().map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        for (bucket, count) in self.buckets.iter().zip(&self.counts) {
+            if value <= *bucket {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let _ = self.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+            Some((f64::from_bits(sum) + value).to_bits())
+        });
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+
+        for (bucket, count) in self.buckets.iter().zip(&self.counts) {
+            let _ = writeln!(
+                out, "{}_bucket{{le=\"{}\"}} {}",
+                self.name, bucket, count.load(Ordering::Relaxed),
+            );
+        }
+
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", self.name, count);
+        let _ = writeln!(
+            out, "{}_sum {}",
+            self.name, f64::from_bits(self.sum.load(Ordering::Relaxed)),
+        );
+        let _ = writeln!(out, "{}_count {}", self.name, count);
+    }
+}
+
+pub struct Metrics {
+    pub scroll_sent: Counter,
+    pub scroll_dropped: Counter,
+    pub scroll_coalesced: Counter,
+    pub scroll_injected: Counter,
+    pub scroll_late: Counter,
+    pub scroll_reconnects: Counter,
+    pub pointer_sent: Counter,
+    pub pointer_dropped: Counter,
+    pub key_sent: Counter,
+    pub key_dropped: Counter,
+    pub gesture_sent: Counter,
+    pub gesture_dropped: Counter,
+    pub clipboard_sent_bytes: Counter,
+    pub clipboard_received_bytes: Counter,
+    pub encryption_failures: Counter,
+    pub activate_latency: Histogram,
+    pub active_changes: Counter,
+    pub active_retries: Counter,
+    pub active_clock: Gauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            scroll_sent: Counter::new(
+                "synq_scroll_events_sent_total",
+                "Scroll events sent to a peer",
+                "peer",
+            ),
+            scroll_dropped: Counter::new(
+                "synq_scroll_events_dropped_total",
+                "Scroll events dropped before delivery",
+                "peer",
+            ),
+            scroll_coalesced: Counter::new(
+                "synq_scroll_events_coalesced_total",
+                "Scroll events merged into a pending event under backpressure",
+                "peer",
+            ),
+            scroll_injected: Counter::new(
+                "synq_scroll_events_injected_total",
+                "Scroll events received from a peer for injection",
+                "peer",
+            ),
+            scroll_late: Counter::new(
+                "synq_scroll_events_late_total",
+                "Scroll events dropped by the jitter buffer for arriving too late",
+                "source",
+            ),
+            scroll_reconnects: Counter::new(
+                "synq_scroll_stream_reconnects_total",
+                "Scroll stream reconnect attempts",
+                "peer",
+            ),
+            pointer_sent: Counter::new(
+                "synq_pointer_events_sent_total",
+                "Pointer motion and button events sent to a peer",
+                "peer",
+            ),
+            pointer_dropped: Counter::new(
+                "synq_pointer_events_dropped_total",
+                "Pointer motion and button events dropped before delivery",
+                "peer",
+            ),
+            key_sent: Counter::new(
+                "synq_key_events_sent_total",
+                "Keyboard events sent to a peer",
+                "peer",
+            ),
+            key_dropped: Counter::new(
+                "synq_key_events_dropped_total",
+                "Keyboard events dropped before delivery",
+                "peer",
+            ),
+            gesture_sent: Counter::new(
+                "synq_gesture_events_sent_total",
+                "Touchpad gesture events sent to a peer",
+                "peer",
+            ),
+            gesture_dropped: Counter::new(
+                "synq_gesture_events_dropped_total",
+                "Touchpad gesture events dropped before delivery",
+                "peer",
+            ),
+            clipboard_sent_bytes: Counter::new(
+                "synq_clipboard_sent_bytes_total",
+                "Encrypted clipboard bytes sent to a peer",
+                "peer",
+            ),
+            clipboard_received_bytes: Counter::new(
+                "synq_clipboard_received_bytes_total",
+                "Encrypted clipboard bytes received from a peer",
+                "peer",
+            ),
+            encryption_failures: Counter::new(
+                "synq_encryption_failures_total",
+                "Clipboard encryption and decryption failures",
+                "operation",
+            ),
+            activate_latency: Histogram::new(
+                "synq_activate_request_seconds",
+                "Activate request round trip latency",
+                LATENCY_BUCKETS,
+            ),
+            active_changes: Counter::new(
+                "synq_active_state_changes_total",
+                "Active state clock changes",
+                "source",
+            ),
+            active_retries: Counter::new(
+                "synq_active_state_retries_total",
+                "Active state deliveries retried after a failed attempt",
+                "peer",
+            ),
+            active_clock: Gauge::new(
+                "synq_active_state_clock",
+                "Current active state clock",
+            ),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        self.scroll_sent.render(&mut out);
+        self.scroll_dropped.render(&mut out);
+        self.scroll_coalesced.render(&mut out);
+        self.scroll_injected.render(&mut out);
+        self.scroll_late.render(&mut out);
+        self.scroll_reconnects.render(&mut out);
+        self.pointer_sent.render(&mut out);
+        self.pointer_dropped.render(&mut out);
+        self.key_sent.render(&mut out);
+        self.key_dropped.render(&mut out);
+        self.gesture_sent.render(&mut out);
+        self.gesture_dropped.render(&mut out);
+        self.clipboard_sent_bytes.render(&mut out);
+        self.clipboard_received_bytes.render(&mut out);
+        self.encryption_failures.render(&mut out);
+        self.activate_latency.render(&mut out);
+        self.active_changes.render(&mut out);
+        self.active_retries.render(&mut out);
+        self.active_clock.render(&mut out);
+
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
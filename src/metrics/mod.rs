@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod metrics;
+pub use metrics::*;
+mod server;
+pub use server::*;
+mod constants;
@@ -0,0 +1,112 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{timeout, Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::{error, info, trace};
+use crate::errors::{Result, Error, ErrorKind};
+
+use super::metrics::METRICS;
+use super::constants::{REQUEST_TIMEOUT, REQUEST_MAX_SIZE};
+
+pub async fn serve(bind: String, cancel: CancellationToken) -> Result<()> {
+    let listener = TcpListener::bind(&bind)
+        .await
+        .map_err(|e| Error::wrap(e, ErrorKind::Network)
+            .with_msg("metrics: Failed to bind metrics server")
+            .with_ctx("bind", &bind))?;
+
+    info!("Metrics server listening on {}", bind);
+
+    loop {
+        let (stream, addr) = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            result = listener.accept() => {
+                match result {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        let e = Error::wrap(e, ErrorKind::Network)
+                            .with_msg("metrics: Failed to accept connection");
+                        error(&e);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        tokio::spawn(async move {
+            let result = timeout(
+                Duration::from_millis(REQUEST_TIMEOUT),
+                handle_request(stream),
+            ).await;
+
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error(&e.with_ctx("address", addr)),
+                Err(_) => trace!("Metrics request timed out from {}", addr),
+            }
+        });
+    }
+}
+
+async fn handle_request(mut stream: TcpStream) -> Result<()> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+
+    loop {
+        let n = stream.read(&mut chunk)
+            .await
+            .map_err(|e| Error::wrap(e, ErrorKind::Read)
+                .with_msg("metrics: Failed to read request"))?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        if buf.windows(4).any(|w| w == b"\r\n\r\n") {
+            break;
+        }
+        if buf.len() > REQUEST_MAX_SIZE {
+            return Err(Error::new(ErrorKind::Invalid)
+                .with_msg("metrics: Request too large"));
+        }
+    }
+
+    let request = String::from_utf8_lossy(&buf);
+    let mut parts = request.lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let response = if method != "GET" {
+        response("405 Method Not Allowed", "text/plain", "method not allowed\n")
+    } else if path == "/metrics" {
+        response(
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            &METRICS.render(),
+        )
+    } else {
+        response("404 Not Found", "text/plain", "not found\n")
+    };
+
+    stream.write_all(response.as_bytes())
+        .await
+        .map_err(|e| Error::wrap(e, ErrorKind::Write)
+            .with_msg("metrics: Failed to write response"))?;
+
+    let _ = stream.shutdown().await;
+
+    Ok(())
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+            Connection: close\r\n\r\n{}",
+        status, content_type, body.len(), body,
+    )
+}
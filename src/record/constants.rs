@@ -0,0 +1,4 @@
+pub(crate) const RECORD_MAGIC: &[u8; 8] = b"SYNQREC1";
+pub(crate) const REPLAY_CONNECT_TIMEOUT: u64 = 5000;
+pub(crate) const REPLAY_CONNECT_POLL: u64 = 100;
+pub(crate) const REPLAY_DRAIN_DELAY: u64 = 500;
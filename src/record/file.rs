@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use prost::Message;
+
+use crate::errors::{Result, Error, ErrorKind};
+use crate::synq::ScrollEvent;
+
+use super::constants::RECORD_MAGIC;
+
+// Recordings are the magic header followed by length-delimited ScrollEvent
+// messages, the same encoding used on the wire
+pub(crate) struct RecordWriter {
+    writer: BufWriter<File>,
+    buf: Vec<u8>,
+    count: usize,
+}
+
+impl RecordWriter {
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .map_err(|e| Error::wrap(e, ErrorKind::Write)
+                .with_msg("record: Failed to create recording")
+                .with_ctx("path", path.display()))?;
+
+        let mut writer = BufWriter::new(file);
+        writer.write_all(RECORD_MAGIC)
+            .map_err(|e| Error::wrap(e, ErrorKind::Write)
+                .with_msg("record: Failed to write recording header"))?;
+
+        Ok(Self {
+            writer,
+            buf: Vec::new(),
+            count: 0,
+        })
+    }
+
+    pub(crate) fn write(&mut self, event: &ScrollEvent) -> Result<()> {
+        self.buf.clear();
+        event.encode_length_delimited(&mut self.buf)
+            .map_err(|e| Error::wrap(e, ErrorKind::Write)
+                .with_msg("record: Failed to encode scroll event"))?;
+
+        self.writer.write_all(&self.buf)
+            .map_err(|e| Error::wrap(e, ErrorKind::Write)
+                .with_msg("record: Failed to write scroll event"))?;
+        self.count += 1;
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<usize> {
+        self.writer.flush()
+            .map_err(|e| Error::wrap(e, ErrorKind::Write)
+                .with_msg("record: Failed to flush recording"))?;
+
+        Ok(self.count)
+    }
+}
+
+pub(crate) fn read_recording(path: &Path) -> Result<Vec<ScrollEvent>> {
+    let data = std::fs::read(path)
+        .map_err(|e| Error::wrap(e, ErrorKind::Read)
+            .with_msg("record: Failed to read recording")
+            .with_ctx("path", path.display()))?;
+
+    let mut rest = data.strip_prefix(RECORD_MAGIC.as_slice())
+        .ok_or_else(|| Error::new(ErrorKind::Parse)
+            .with_msg("record: Not a synq recording")
+            .with_ctx("path", path.display()))?;
+
+    let mut events = Vec::new();
+    while !rest.is_empty() {
+        let event = ScrollEvent::decode_length_delimited(&mut rest)
+            .map_err(|e| Error::wrap(e, ErrorKind::Parse)
+                .with_msg("record: Failed to decode scroll event")
+                .with_ctx("index", events.len()))?;
+        events.push(event);
+    }
+
+    Ok(events)
+}
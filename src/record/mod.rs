@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod record;
+mod constants;
+mod file;
+
+pub use record::*;
@@ -0,0 +1,288 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::{Result, Error, ErrorKind};
+use crate::config::{Config, PeerConfig, ScrollOutput};
+use crate::crypto::KeyStore;
+use crate::scroll::{
+    ScrollReceiver,
+    ScrollSender,
+    ScrollPipeline,
+    ScrollSource,
+    SharedUinput,
+    UinputCapabilities,
+    ReceiverEvent,
+    ScrollGenerator,
+    SCROLL_DEVICE_ID,
+    SCROLL_DEVICE_NAME,
+    resolve_devices,
+    scroll_source,
+};
+use crate::synq::{ScrollEvent, ScrollSource as ProtoScrollSource};
+use crate::transport::{Transport, PeerState};
+use crate::utils;
+
+use super::constants::{REPLAY_CONNECT_POLL, REPLAY_CONNECT_TIMEOUT, REPLAY_DRAIN_DELAY};
+use super::file::{RecordWriter, read_recording};
+
+pub async fn record(config: Config, path: &Path, device: Option<String>) -> Result<()> {
+    let device_path = input_device_path(&config, device)?;
+    let writer = RecordWriter::create(path)?;
+
+    println!("Recording scroll from {}... Press Ctrl+C to stop.", device_path);
+
+    let cancel = CancellationToken::new();
+    let mut handle = tokio::task::spawn_blocking({
+        let cancel = cancel.clone();
+
+        move || record_events(&device_path, writer, cancel)
+    });
+
+    let result = tokio::select! {
+        result = &mut handle => result,
+        _ = tokio::signal::ctrl_c() => {
+            cancel.cancel();
+            handle.await
+        }
+    };
+    let writer = result.map_err(|e| Error::wrap(e, ErrorKind::Exec)
+        .with_msg("record: Recording task failed"))??;
+
+    let count = writer.finish()?;
+    println!("Recorded {} scroll event(s) to {}", count, path.display());
+
+    Ok(())
+}
+
+fn record_events(
+    device_path: &str,
+    mut writer: RecordWriter,
+    cancel: CancellationToken,
+) -> Result<RecordWriter> {
+    let mut receiver = ScrollReceiver::new(device_path)?;
+
+    while !cancel.is_cancelled() {
+        if let Some(ReceiverEvent::Scroll(event)) = receiver.read_event()? {
+            let source = match event.source {
+                ScrollSource::Wheel => ProtoScrollSource::Wheel,
+                ScrollSource::Finger => ProtoScrollSource::Finger,
+                ScrollSource::Continuous => ProtoScrollSource::Continuous,
+            };
+
+            writer.write(&ScrollEvent {
+                source: source.into(),
+                delta_x: event.delta_x,
+                delta_y: event.delta_y,
+                time_usec: event.time_usec,
+                stop: event.stop,
+                mirror: false,
+            })?;
+        }
+    }
+
+    Ok(writer)
+}
+
+pub async fn replay(
+    config: Config,
+    path: &Path,
+    peer: Option<String>,
+    device: Option<String>,
+) -> Result<()> {
+    let events = read_recording(path)?;
+    if events.is_empty() {
+        println!("Recording {} is empty", path.display());
+        return Ok(());
+    }
+
+    play(config, events, peer, device).await
+}
+
+pub async fn scroll_test(
+    config: Config,
+    generator: ScrollGenerator,
+    peer: Option<String>,
+    device: Option<String>,
+) -> Result<()> {
+    play(config, generator.events(), peer, device).await
+}
+
+async fn play(
+    config: Config,
+    events: Vec<ScrollEvent>,
+    peer: Option<String>,
+    device: Option<String>,
+) -> Result<()> {
+    let base_time = events.first().map_or(0, |e| e.time_usec);
+
+    let target = match &peer {
+        Some(peer) => {
+            let target = config.peers.iter()
+                .find(|p| p.scroll_destination && (p.address == *peer || p.public_key == *peer))
+                .cloned()
+                .ok_or_else(|| Error::new(ErrorKind::NotFound)
+                    .with_msg("record: No scroll destination peer matches")
+                    .with_ctx("peer", peer))?;
+            Some(target)
+        }
+        None => None,
+    };
+
+    let key_store = Arc::new(
+        KeyStore::new(&config.server.private_key)
+        .map_err(|e| Error::wrap(e, ErrorKind::Exec)
+            .with_msg("record: Failed to create key store"))?);
+    let transport = Transport::new(&replay_config(&config, target.as_ref()), key_store).await?;
+
+    let mut sender = match &target {
+        Some(target) => {
+            transport.active_state().increment_and_set(target.public_key.clone());
+            wait_connected(&transport, &target.address).await?;
+            None
+        }
+        None => {
+            Some(ScrollSender::new(
+                create_uinput(&config, device)?,
+                transport.clone(),
+                ScrollPipeline::new(config.server.scroll_transform.clone()),
+                config.server.scroll_output.unwrap_or(ScrollOutput::Both),
+            ))
+        }
+    };
+
+    match &target {
+        Some(target) => println!("Sending {} scroll event(s) to {}", events.len(), target.address),
+        None => println!("Injecting {} scroll event(s) locally", events.len()),
+    }
+
+    // Events keep their original spacing and are stamped with the current
+    // time so the destination jitter buffer sees them as live scroll
+    let start = Instant::now();
+    let start_usec = utils::mono_time_us();
+    let mut count = 0;
+
+    let playback = async {
+        for mut event in events {
+            let offset = event.time_usec.saturating_sub(base_time);
+            sleep_until(start + Duration::from_micros(offset)).await;
+
+            match sender.as_mut() {
+                Some(sender) => sender.send(scroll_source(&event), event.delta_x, event.delta_y)?,
+                None => {
+                    event.time_usec = start_usec + offset;
+                    transport.send_scroll(event);
+                }
+            }
+            count += 1;
+        }
+
+        Ok::<_, Error>(())
+    };
+
+    let result = tokio::select! {
+        result = playback => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+
+    if target.is_some() {
+        sleep(Duration::from_millis(REPLAY_DRAIN_DELAY)).await;
+    }
+    transport.shutdown();
+    result?;
+
+    println!("Sent {} scroll event(s) in {} ms", count, start.elapsed().as_millis());
+
+    Ok(())
+}
+
+// Only the scroll path is needed, so the playback transport runs no server
+// and shares nothing but scroll with the target peer
+fn replay_config(config: &Config, target: Option<&PeerConfig>) -> Config {
+    let mut replay = config.clone();
+
+    let server = &mut replay.server;
+    server.clipboard_source = false;
+    server.clipboard_destination = false;
+    server.scroll_source = false;
+    server.scroll_destination = false;
+    server.pointer_sharing = false;
+    server.keyboard_sharing = false;
+    server.gesture_sharing = false;
+    server.scroll_mirror = false;
+
+    replay.peers = target.into_iter()
+        .map(|peer| PeerConfig {
+            clipboard_source: false,
+            clipboard_destination: false,
+            mirror: false,
+            session: false,
+            ..peer.clone()
+        })
+        .collect();
+
+    replay
+}
+
+async fn wait_connected(transport: &Transport, address: &str) -> Result<()> {
+    let deadline = Instant::now() + Duration::from_millis(REPLAY_CONNECT_TIMEOUT);
+
+    while Instant::now() < deadline {
+        let connected = transport.status().await.iter()
+            .any(|(addr, state)| addr == address && matches!(state, PeerState::Connected));
+        if connected {
+            return Ok(());
+        }
+        sleep(Duration::from_millis(REPLAY_CONNECT_POLL)).await;
+    }
+
+    Err(Error::new(ErrorKind::Timeout)
+        .with_msg("record: Timed out connecting to peer")
+        .with_ctx("address", address))
+}
+
+fn input_device_path(config: &Config, device: Option<String>) -> Result<String> {
+    if let Some(device) = device {
+        return Ok(device);
+    }
+
+    resolve_devices(&config.server.scroll_input_devices)?
+        .into_iter()
+        .next()
+        .map(|d| d.path)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound)
+            .with_msg("record: No scroll input device found, pass --device"))
+}
+
+// Injects through a clone of the given or first scroll input device, or a
+// standalone virtual wheel when there is none
+fn create_uinput(config: &Config, device: Option<String>) -> Result<SharedUinput> {
+    let device_path = match device {
+        Some(device) => Some(device),
+        None => resolve_devices(&config.server.scroll_input_devices)?
+            .into_iter()
+            .next()
+            .map(|d| d.path),
+    };
+
+    let Some(device_path) = device_path else {
+        return SharedUinput::standalone(SCROLL_DEVICE_NAME, SCROLL_DEVICE_ID)
+            .map_err(|e| Error::wrap(e, ErrorKind::Exec)
+                .with_msg("record: Failed to create uinput device"));
+    };
+
+    let source_file = std::fs::OpenOptions::new()
+        .read(true)
+        .open(&device_path)
+        .map_err(|e| Error::wrap(e, ErrorKind::Read)
+            .with_msg("record: Failed to open scroll device for uinput setup")
+            .with_ctx("path", &device_path))?;
+    let source_fd = std::os::unix::io::AsRawFd::as_raw_fd(&source_file);
+
+    SharedUinput::new(source_fd, SCROLL_DEVICE_NAME, SCROLL_DEVICE_ID, UinputCapabilities::default())
+        .map_err(|e| Error::wrap(e, ErrorKind::Exec)
+            .with_msg("record: Failed to create uinput device")
+            .with_ctx("path", &device_path))
+}
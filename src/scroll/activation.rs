@@ -0,0 +1,105 @@
+use crate::config::{ActivationTrigger, ScrollActivation};
+
+use super::constants::{
+    EV_ABS,
+    EV_KEY,
+    EV_REL,
+    ABS_X,
+    ABS_Y,
+    REL_X,
+    REL_Y,
+    REL_WHEEL,
+    REL_HWHEEL,
+    REL_WHEEL_HI_RES,
+    REL_HWHEEL_HI_RES,
+};
+
+pub type PointerLocator = Box<dyn Fn() -> Option<(i32, i32)> + Send>;
+
+pub struct ActivationFilter {
+    activation: ScrollActivation,
+    locator: Option<PointerLocator>,
+    burst_start: Option<u64>,
+    last_trigger: u64,
+    last_request: Option<u64>,
+    last_query: Option<u64>,
+}
+
+impl ActivationFilter {
+    pub fn new(activation: ScrollActivation, locator: Option<PointerLocator>) -> Self {
+        Self {
+            activation,
+            locator,
+            burst_start: None,
+            last_trigger: 0,
+            last_request: None,
+            last_query: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.burst_start = None;
+    }
+
+    pub fn check(&mut self, type_: u16, code: u16, value: i32, now: u64) -> bool {
+        if !self.triggers(type_, code, value) {
+            return false;
+        }
+
+        // Activity has to continue for the debounce period, a pause longer
+        // than the period starts over
+        let burst_start = match self.burst_start {
+            Some(start) if now.saturating_sub(self.last_trigger) <= self.activation.debounce => start,
+            _ => now,
+        };
+        self.burst_start = Some(burst_start);
+        self.last_trigger = now;
+
+        if now - burst_start < self.activation.debounce {
+            return false;
+        }
+
+        if self.last_request.is_some_and(|t| now.saturating_sub(t) < self.activation.rate_limit) {
+            return false;
+        }
+
+        // Every motion event outside the region would otherwise ask the
+        // display server for the pointer, misses share the request rate limit
+        if self.activation.trigger == ActivationTrigger::Region {
+            if self.last_query.is_some_and(|t| now.saturating_sub(t) < self.activation.rate_limit) {
+                return false;
+            }
+            self.last_query = Some(now);
+
+            if !self.in_region() {
+                return false;
+            }
+        }
+
+        self.last_request = Some(now);
+        true
+    }
+
+    fn triggers(&self, type_: u16, code: u16, value: i32) -> bool {
+        let is_motion = (type_ == EV_REL as u16 && (code == REL_X || code == REL_Y))
+            || (type_ == EV_ABS as u16 && (code == ABS_X || code == ABS_Y));
+
+        match self.activation.trigger {
+            ActivationTrigger::Scroll => type_ == EV_REL as u16
+                && (code == REL_WHEEL
+                    || code == REL_HWHEEL
+                    || code == REL_WHEEL_HI_RES
+                    || code == REL_HWHEEL_HI_RES),
+            ActivationTrigger::Motion | ActivationTrigger::Region => is_motion,
+            ActivationTrigger::Button => type_ == EV_KEY as u16 && value == 1,
+        }
+    }
+
+    fn in_region(&self) -> bool {
+        let (Some(region), Some(locator)) = (&self.activation.region, &self.locator) else {
+            return false;
+        };
+
+        locator().is_some_and(|(x, y)| region.contains(x, y))
+    }
+}
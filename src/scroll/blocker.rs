@@ -12,12 +12,14 @@ use crate::errors::{Error, ErrorKind, Result};
 use crate::transport::ActiveState;
 use super::constants::{
     EVIOCGRAB,
+    POLL_TIMEOUT,
     EV_REL,
     REL_WHEEL,
     REL_HWHEEL,
     REL_WHEEL_HI_RES,
     REL_HWHEEL_HI_RES,
 };
+use super::activation::ActivationFilter;
 use super::event::InputEvent;
 use super::utils::SharedUinput;
 
@@ -25,7 +27,8 @@ pub struct ScrollBlocker {
     device: File,
     uinput: SharedUinput,
     active_state: ActiveState,
-    on_scroll: Option<Box<dyn Fn() + Send>>,
+    activation: ActivationFilter,
+    on_activate: Option<Box<dyn Fn() + Send>>,
 }
 
 impl ScrollBlocker {
@@ -33,7 +36,8 @@ impl ScrollBlocker {
         device_path: impl AsRef<Path>,
         uinput: SharedUinput,
         active_state: ActiveState,
-        on_scroll: Option<Box<dyn Fn() + Send>>,
+        activation: ActivationFilter,
+        on_activate: Option<Box<dyn Fn() + Send>>,
     ) -> Result<Self> {
         let path = device_path.as_ref();
 
@@ -58,7 +62,8 @@ impl ScrollBlocker {
             device,
             uinput,
             active_state,
-            on_scroll,
+            activation,
+            on_activate,
         })
     }
 
@@ -72,8 +77,11 @@ impl ScrollBlocker {
 
         // Read type and code directly from buffer without full transmute
         // InputEvent layout: tv_sec (8), tv_usec (8), type (2), code (2), value (4)
-        if u16::from_ne_bytes([buf[16], buf[17]]) == EV_REL as u16 {
-            let code = u16::from_ne_bytes([buf[18], buf[19]]);
+        let type_ = u16::from_ne_bytes([buf[16], buf[17]]);
+        let code = u16::from_ne_bytes([buf[18], buf[19]]);
+        let value = i32::from_ne_bytes([buf[20], buf[21], buf[22], buf[23]]);
+
+        if type_ == EV_REL as u16 {
             if code == REL_WHEEL
                 || code == REL_HWHEEL
                 || code == REL_WHEEL_HI_RES
@@ -88,10 +96,12 @@ impl ScrollBlocker {
             self.uinput.write_raw(&buf)?;
         }
 
-        if !self.active_state.is_host_active() {
+        if self.active_state.is_host_active() {
+            self.activation.reset();
+        } else if self.activation.check(type_, code, value, mono_time_ms()) {
             trace!("Not active, sending active request");
-            if let Some(ref on_scroll) = self.on_scroll {
-                on_scroll();
+            if let Some(ref on_activate) = self.on_activate {
+                on_activate();
             }
         }
 
@@ -99,7 +109,29 @@ impl ScrollBlocker {
     }
 
     pub fn run(&mut self, cancel: CancellationToken) -> Result<()> {
+        let fd = self.device.as_raw_fd();
+
         while !cancel.is_cancelled() {
+            let mut pfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            let ret = unsafe { libc::poll(&mut pfd, 1, POLL_TIMEOUT) };
+            if ret < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(Error::wrap(err, ErrorKind::Read)
+                    .with_msg("scroll: Poll failed"));
+            }
+
+            if ret == 0 {
+                continue;
+            }
+
             self.process_events()?;
         }
         Ok(())
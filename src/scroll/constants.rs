@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 pub(crate) const EV_MAX: usize = 0x1f;
 pub(crate) const KEY_MAX: usize = 0x2ff;
 pub(crate) const REL_MAX: usize = 0x0f;
@@ -15,6 +17,7 @@ pub(crate) const EV_SND: libc::c_int = 0x12;
 pub(crate) const EV_FF: libc::c_int = 0x15;
 
 pub(crate) const EVIOCGRAB: libc::c_ulong = 0x40044590;
+pub(crate) const EVIOCSCLOCKID: libc::c_ulong = 0x400445a0;
 #[allow(unused)]
 pub(crate) const EVIOCGID: libc::c_ulong = (2u64 << 30) | (8u64 << 16) | (0x45u64 << 8) | 0x02;
 
@@ -27,6 +30,7 @@ pub(crate) const UI_SET_LEDBIT: libc::c_ulong = 0x40045569;
 pub(crate) const UI_SET_SNDBIT: libc::c_ulong = 0x4004556a;
 pub(crate) const UI_SET_FFBIT: libc::c_ulong = 0x4004556b;
 pub(crate) const UI_SET_SWBIT: libc::c_ulong = 0x4004556d;
+pub(crate) const UI_SET_PROPBIT: libc::c_ulong = 0x4004556e;
 pub(crate) const UI_ABS_SETUP: libc::c_ulong = 0x401c5504;
 pub(crate) const UI_DEV_SETUP: libc::c_ulong = 0x405c5503;
 pub(crate) const UI_DEV_CREATE: libc::c_ulong = 0x5501;
@@ -36,6 +40,8 @@ pub(crate) const EV_KEY: libc::c_int = 0x01;
 pub(crate) const EV_REL: libc::c_int = 0x02;
 pub(crate) const EV_ABS: libc::c_int = 0x03;
 
+pub(crate) const REL_X: u16 = 0x00;
+pub(crate) const REL_Y: u16 = 0x01;
 pub(crate) const REL_HWHEEL: u16 = 0x06;
 pub(crate) const REL_WHEEL: u16 = 0x08;
 pub(crate) const REL_WHEEL_HI_RES: u16 = 0x0b;
@@ -43,36 +49,82 @@ pub(crate) const REL_HWHEEL_HI_RES: u16 = 0x0c;
 
 pub(crate) const SYN_REPORT: u16 = 0x00;
 
-#[allow(unused)]
+pub(crate) const ABS_X: u16 = 0x00;
+pub(crate) const ABS_Y: u16 = 0x01;
 pub(crate) const ABS_MT_SLOT: u16 = 0x2f;
-#[allow(unused)]
 pub(crate) const ABS_MT_TRACKING_ID: u16 = 0x39;
-#[allow(unused)]
 pub(crate) const ABS_MT_POSITION_X: u16 = 0x35;
-#[allow(unused)]
 pub(crate) const ABS_MT_POSITION_Y: u16 = 0x36;
 
-#[allow(unused)]
+pub(crate) const INPUT_PROP_POINTER: libc::c_int = 0x00;
+pub(crate) const INPUT_PROP_BUTTONPAD: libc::c_int = 0x02;
+
+pub(crate) const KEY_ESC: u16 = 0x01;
+pub(crate) const KEY_LEFTCTRL: u16 = 0x1d;
+pub(crate) const BTN_MISC: u16 = 0x100;
+
+pub(crate) const BTN_LEFT: u16 = 0x110;
+pub(crate) const BTN_TASK: u16 = 0x117;
+
 pub(crate) const BTN_TOUCH: u16 = 0x14a;
-#[allow(unused)]
+// Relative counts per millimeter of touchpad travel when forwarding
+// absolute touchpad motion as pointer motion
+pub(crate) const POINTER_COUNTS_PER_MM: f64 = 10.0;
 pub(crate) const BTN_TOOL_FINGER: u16 = 0x145;
-#[allow(unused)]
+pub(crate) const BTN_TOOL_QUINTTAP: u16 = 0x148;
 pub(crate) const BTN_TOOL_DOUBLETAP: u16 = 0x14d;
+pub(crate) const BTN_TOOL_TRIPLETAP: u16 = 0x14e;
+pub(crate) const BTN_TOOL_QUADTAP: u16 = 0x14f;
 
-pub(crate) const SCROLL_DEVICE_NAME: &[u8] = b"Virtual Scroll Device";
+pub(crate) const UINPUT_MAX_NAME_SIZE: usize = 80;
+pub(crate) const SCROLL_DEVICE_NAME: &str = "Virtual Scroll Device";
 pub(crate) const SCROLL_DEVICE_ID: [u16; 4] = [0x06, 0x628, 0x1, 0x1];
 
-#[allow(unused)]
 pub(crate) const TOUCHPAD_DEVICE_NAME: &[u8] = b"Virtual Touchpad";
-#[allow(unused)]
 pub(crate) const TOUCHPAD_DEVICE_ID: [u16; 4] = [0x03, 0x0001, 0x0001, 0x0001];
+pub(crate) const TOUCHPAD_MAX_X: i32 = 4000;
+pub(crate) const TOUCHPAD_MAX_Y: i32 = 2500;
+pub(crate) const TOUCHPAD_RESOLUTION: i32 = 40;
+pub(crate) const TOUCHPAD_SLOTS: usize = 5;
+pub(crate) const TOUCHPAD_FINGER_SPREAD: f64 = 300.0;
+
+pub(crate) const POLL_TIMEOUT: libc::c_int = 100;
+
+pub(crate) const KEYBOARD_PANIC_HOTKEY: &str = "ctrl+alt+esc";
+
+pub(crate) const JITTER_LATENCY_BUDGET: u64 = 50;
+pub(crate) const JITTER_MIN_DELAY_US: u64 = 4000;
+pub(crate) const JITTER_GAP_US: u64 = 250_000;
+
+pub(crate) const KINETIC_INTERVAL_US: u64 = 16_000;
+pub(crate) const KINETIC_WINDOW_US: u64 = 100_000;
+
+pub(crate) const BEZIER_ITERATIONS: usize = 24;
+
+// libinput reports 15 degrees per wheel click and v120 uses 120 per click
+pub(crate) const V120_PER_CLICK: i32 = 120;
+pub(crate) const V120_PER_DELTA: f64 = 8.0;
+pub(crate) const DELTA_PER_CLICK: f64 = V120_PER_CLICK as f64 / V120_PER_DELTA;
+
+pub(crate) const SCROLL_TEST_DURATION: u64 = 2000;
+pub(crate) const SCROLL_TEST_INTERVAL: u64 = 10;
+pub(crate) const SCROLL_TEST_SPEED: f64 = 300.0;
 
 pub(crate) const SCROLL_TTL: u64 = 120;
 pub(crate) const BLUR_TTL: u64 = 50;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ScrollSource {
     Wheel,
     Finger,
     Continuous,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GesturePhase {
+    Begin,
+    Update,
+    End,
+    Cancel,
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fs::{File, OpenOptions};
 use std::os::unix::fs::OpenOptionsExt;
@@ -9,9 +10,12 @@ use input::event::pointer::PointerEvent;
 use input::event::EventTrait;
 use input::{DeviceCapability, Event, Libinput, LibinputInterface, ScrollMethod};
 use libc::{O_ACCMODE, O_RDONLY, O_RDWR, O_WRONLY};
+use serde::Serialize;
 
-use crate::config::{Config, InputDevice};
+use crate::config::{Config, InputDevice, ScrollActivation};
 use crate::errors::{Error, ErrorKind, Result};
+use super::constants::{SCROLL_DEVICE_NAME, SCROLL_DEVICE_ID};
+use super::utils::is_own_device;
 
 struct Interface;
 
@@ -41,42 +45,190 @@ impl LibinputInterface for Interface {
 pub struct Device {
     pub name: String,
     pub path: String,
+    pub sysname: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub bustype: Option<u16>,
+    pub seat: String,
+    pub size: Option<(f64, f64)>,
+    pub phys: Option<String>,
+    pub uniq: Option<String>,
+    pub properties: BTreeMap<String, String>,
     pub capabilities: Vec<DeviceCapability>,
     pub scroll_methods: Vec<ScrollMethod>,
     pub has_scroll: bool,
 }
 
+impl Device {
+    pub fn from_libinput(dev: &input::Device) -> Self {
+        let capabilities = [
+            DeviceCapability::Keyboard,
+            DeviceCapability::Pointer,
+            DeviceCapability::Touch,
+            DeviceCapability::TabletTool,
+            DeviceCapability::TabletPad,
+            DeviceCapability::Gesture,
+            DeviceCapability::Switch,
+        ]
+        .into_iter()
+        .filter(|cap| dev.has_capability(*cap))
+        .collect();
+
+        let scroll_methods = dev.config_scroll_methods();
+
+        let has_scroll = scroll_methods.iter().any(|m| {
+            matches!(
+                m,
+                ScrollMethod::TwoFinger | ScrollMethod::Edge | ScrollMethod::OnButtonDown,
+            )
+        });
+
+        // Devices come from a udev backed libinput context
+        let udev_device = unsafe { dev.udev_device() };
+        let properties = udev_device.as_ref()
+            .map(|d| d.properties()
+                .map(|p| (
+                    p.name().to_string_lossy().into_owned(),
+                    p.value().to_string_lossy().into_owned(),
+                ))
+                .collect())
+            .unwrap_or_default();
+
+        // phys and uniq are attributes of the parent input device
+        let input_parent = udev_device.as_ref().and_then(|d| d.parent());
+        let attribute = |name: &str| input_parent.as_ref()
+            .and_then(|p| p.attribute_value(name))
+            .map(|v| v.to_string_lossy().trim().to_string())
+            .filter(|v| !v.is_empty());
+        let bustype = attribute("id/bustype")
+            .and_then(|v| u16::from_str_radix(&v, 16).ok());
+
+        Self {
+            name: dev.name().to_string(),
+            path: format!("/dev/input/{}", dev.sysname()),
+            sysname: dev.sysname().to_string(),
+            vendor_id: dev.id_vendor() as u16,
+            product_id: dev.id_product() as u16,
+            bustype,
+            seat: dev.seat().logical_name().to_string(),
+            size: dev.size(),
+            phys: attribute("phys"),
+            uniq: attribute("uniq"),
+            properties,
+            capabilities,
+            scroll_methods,
+            has_scroll,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceReport {
+    pub name: String,
+    pub path: String,
+    pub sysname: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bustype: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phys: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uniq: Option<String>,
+    pub seat: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_mm: Option<[f64; 2]>,
+    pub capabilities: Vec<&'static str>,
+    pub scroll_methods: Vec<&'static str>,
+    pub has_scroll: bool,
+    pub matched_by: Vec<&'static str>,
+    pub tags: Vec<String>,
+    pub properties: BTreeMap<String, String>,
+}
+
+impl Device {
+    pub fn report(&self, config: Option<&Config>) -> DeviceReport {
+        let mut matched_by = Vec::new();
+        if let Some(config) = config {
+            if config.server.scroll_input_devices.iter().any(|d| d.matches(self)) {
+                matched_by.push("scroll_input_devices");
+            }
+            if config.server.keyboard_input_devices.iter().any(|d| d.matches(self)) {
+                matched_by.push("keyboard_input_devices");
+            }
+        }
+
+        let tags = self.properties.get("TAGS")
+            .map(|t| t.split(':').filter(|t| !t.is_empty()).map(String::from).collect())
+            .unwrap_or_default();
+
+        DeviceReport {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            sysname: self.sysname.clone(),
+            vendor_id: self.vendor_id,
+            product_id: self.product_id,
+            bustype: self.bustype,
+            phys: self.phys.clone(),
+            uniq: self.uniq.clone(),
+            seat: self.seat.clone(),
+            size_mm: self.size.map(|(w, h)| [w, h]),
+            capabilities: self.capabilities.iter().map(|c| capability_name(*c)).collect(),
+            scroll_methods: self.scroll_methods.iter().map(|m| scroll_method_name(*m)).collect(),
+            has_scroll: self.has_scroll,
+            matched_by,
+            tags,
+            properties: self.properties.clone(),
+        }
+    }
+}
+
+fn capability_name(capability: DeviceCapability) -> &'static str {
+    match capability {
+        DeviceCapability::Keyboard => "keyboard",
+        DeviceCapability::Pointer => "pointer",
+        DeviceCapability::Touch => "touch",
+        DeviceCapability::TabletTool => "tablet-tool",
+        DeviceCapability::TabletPad => "tablet-pad",
+        DeviceCapability::Gesture => "gesture",
+        DeviceCapability::Switch => "switch",
+        _ => "unknown",
+    }
+}
+
+fn scroll_method_name(method: ScrollMethod) -> &'static str {
+    match method {
+        ScrollMethod::NoScroll => "none",
+        ScrollMethod::TwoFinger => "two-finger",
+        ScrollMethod::Edge => "edge",
+        ScrollMethod::OnButtonDown => "button",
+        _ => "unknown",
+    }
+}
+
 impl fmt::Display for Device {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Device: {}", self.name)?;
         writeln!(f, "  Path: {}", self.path)?;
+        writeln!(f, "  ID: {:04x}:{:04x}", self.vendor_id, self.product_id)?;
+        if let Some(phys) = &self.phys {
+            writeln!(f, "  Phys: {}", phys)?;
+        }
+        if let Some(uniq) = &self.uniq {
+            writeln!(f, "  Uniq: {}", uniq)?;
+        }
 
         let caps: Vec<&str> = self
             .capabilities
             .iter()
-            .map(|c| match c {
-                DeviceCapability::Keyboard => "keyboard",
-                DeviceCapability::Pointer => "pointer",
-                DeviceCapability::Touch => "touch",
-                DeviceCapability::TabletTool => "tablet-tool",
-                DeviceCapability::TabletPad => "tablet-pad",
-                DeviceCapability::Gesture => "gesture",
-                DeviceCapability::Switch => "switch",
-                _ => "unknown",
-            })
+            .map(|c| capability_name(*c))
             .collect();
         writeln!(f, "  Capabilities: {}", caps.join(" "))?;
 
         let methods: Vec<&str> = self
             .scroll_methods
             .iter()
-            .map(|m| match m {
-                ScrollMethod::NoScroll => "none",
-                ScrollMethod::TwoFinger => "two-finger",
-                ScrollMethod::Edge => "edge",
-                ScrollMethod::OnButtonDown => "button",
-                _ => "unknown",
-            })
+            .map(|m| scroll_method_name(*m))
             .collect();
         writeln!(f, "  Has Scroll: {}", self.has_scroll)?;
         write!(f, "  Scroll methods: {}", methods.join(" "))
@@ -96,37 +248,7 @@ pub fn list_devices() -> Result<Vec<Device>> {
     let mut devices = Vec::new();
     for event in &mut libinput {
         if let input::Event::Device(input::event::DeviceEvent::Added(event)) = event {
-            let dev = event.device();
-
-            let capabilities = [
-                DeviceCapability::Keyboard,
-                DeviceCapability::Pointer,
-                DeviceCapability::Touch,
-                DeviceCapability::TabletTool,
-                DeviceCapability::TabletPad,
-                DeviceCapability::Gesture,
-                DeviceCapability::Switch,
-            ]
-            .into_iter()
-            .filter(|cap| dev.has_capability(*cap))
-            .collect();
-
-            let scroll_methods = dev.config_scroll_methods();
-
-            let has_scroll = scroll_methods.iter().any(|m| {
-                matches!(
-                    m,
-                    ScrollMethod::TwoFinger | ScrollMethod::Edge | ScrollMethod::OnButtonDown,
-                )
-            });
-
-            devices.push(Device {
-                name: dev.name().to_string(),
-                path: format!("/dev/input/{}", dev.sysname()),
-                capabilities,
-                scroll_methods,
-                has_scroll,
-            });
+            devices.push(Device::from_libinput(&event.device()));
         }
     }
 
@@ -139,26 +261,25 @@ pub struct ResolvedDevice {
     pub scroll_reverse: bool,
     #[allow(unused)]
     pub scroll_modifier: f64,
+    pub virtual_name: String,
+    pub virtual_id: [u16; 4],
+    pub scroll_target: bool,
+    pub activation: ScrollActivation,
 }
 
 pub fn resolve_devices(input_devices: &[InputDevice]) -> Result<Vec<ResolvedDevice>> {
-    let devices = list_devices()?;
+    let mut devices = list_devices()?;
+    devices.retain(|d| !d.properties.get("DEVPATH").is_some_and(|p| is_own_device(p)));
     let mut resolved = Vec::new();
 
     for input in input_devices {
-        let matches_input = |d: &Device| {
-            let path_matches = input.path.as_ref().is_some_and(|p| d.path == *p);
-            let name_matches = input.name.as_ref().is_some_and(|n| d.name.eq_ignore_ascii_case(n));
-            path_matches || name_matches
-        };
-
         let matched: Vec<_> = devices
             .iter()
-            .filter(|d| d.has_scroll && matches_input(d))
+            .filter(|d| d.has_scroll && input.matches(d))
             .collect();
 
         let matched = if matched.is_empty() {
-            devices.iter().filter(|d| matches_input(d)).collect()
+            devices.iter().filter(|d| input.matches(d)).collect()
         } else {
             matched
         };
@@ -168,6 +289,12 @@ pub fn resolve_devices(input_devices: &[InputDevice]) -> Result<Vec<ResolvedDevi
                 path: device.path.clone(),
                 scroll_reverse: input.scroll_reverse,
                 scroll_modifier: input.scroll_modifier,
+                virtual_name: input.virtual_name.clone()
+                    .unwrap_or_else(|| SCROLL_DEVICE_NAME.to_string()),
+                virtual_id: input.virtual_id
+                    .map_or(SCROLL_DEVICE_ID, |id| id.as_array()),
+                scroll_target: input.scroll_target,
+                activation: input.activation.clone().unwrap_or_default(),
             });
         }
     }
@@ -1,9 +1,29 @@
-use super::constants::{ScrollSource};
+use crate::config::GestureKind;
+
+use super::constants::{ScrollSource, GesturePhase};
 
 pub struct ScrollEvent {
     pub source: ScrollSource,
     pub delta_x: f64,
     pub delta_y: f64,
+    pub time_usec: u64,
+    pub stop: bool,
+}
+
+pub struct GestureInput {
+    pub kind: GestureKind,
+    pub phase: GesturePhase,
+    pub fingers: u32,
+    pub dx: f64,
+    pub dy: f64,
+    pub scale: f64,
+    pub angle_delta: f64,
+    pub time_usec: u64,
+}
+
+pub enum ReceiverEvent {
+    Scroll(ScrollEvent),
+    Gesture(GestureInput),
 }
 
 #[repr(C)]
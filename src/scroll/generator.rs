@@ -0,0 +1,87 @@
+use clap::ValueEnum;
+
+use crate::synq::{ScrollEvent, ScrollSource};
+
+use super::constants::DELTA_PER_CLICK;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScrollPattern {
+    Constant,
+    Fling,
+    Wheel,
+    Diagonal,
+}
+
+// Produces synthetic scroll as a source would send it, with timestamps
+// relative to the start of the pattern
+#[derive(Debug, Clone)]
+pub struct ScrollGenerator {
+    pattern: ScrollPattern,
+    duration_ms: u64,
+    interval_ms: u64,
+    speed: f64,
+}
+
+impl ScrollGenerator {
+    pub fn new(pattern: ScrollPattern, duration_ms: u64, interval_ms: u64, speed: f64) -> Self {
+        Self {
+            pattern,
+            duration_ms,
+            interval_ms: interval_ms.max(1),
+            speed,
+        }
+    }
+
+    pub fn events(&self) -> Vec<ScrollEvent> {
+        let steps = self.duration_ms / self.interval_ms;
+        let step_delta = self.speed * self.interval_ms as f64 / 1000.0;
+
+        let mut events: Vec<_> = (1..=steps)
+            .map(|step| {
+                let time_usec = step * self.interval_ms * 1000;
+                match self.pattern {
+                    ScrollPattern::Constant => finger(time_usec, 0.0, step_delta),
+                    // Speeds up from rest to twice the speed and lifts at the
+                    // peak so the destination can carry it on as kinetic scroll
+                    ScrollPattern::Fling => {
+                        let progress = step as f64 / steps as f64;
+                        finger(time_usec, 0.0, 2.0 * step_delta * progress)
+                    }
+                    ScrollPattern::Wheel => ScrollEvent {
+                        source: ScrollSource::Wheel.into(),
+                        delta_x: 0.0,
+                        delta_y: DELTA_PER_CLICK * self.speed.signum(),
+                        time_usec,
+                        stop: false,
+                        mirror: false,
+                    },
+                    ScrollPattern::Diagonal => {
+                        let delta = step_delta / std::f64::consts::SQRT_2;
+                        finger(time_usec, delta, delta)
+                    }
+                }
+            })
+            .collect();
+
+        if self.pattern != ScrollPattern::Wheel {
+            let time_usec = (steps + 1) * self.interval_ms * 1000;
+            events.push(ScrollEvent {
+                stop: true,
+                ..finger(time_usec, 0.0, 0.0)
+            });
+        }
+
+        events
+    }
+}
+
+fn finger(time_usec: u64, delta_x: f64, delta_y: f64) -> ScrollEvent {
+    ScrollEvent {
+        source: ScrollSource::Finger.into(),
+        delta_x,
+        delta_y,
+        time_usec,
+        stop: false,
+        mirror: false,
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+
+use crate::errors::trace;
+use crate::synq::ScrollEvent;
+
+use super::constants::{JITTER_MIN_DELAY_US, JITTER_GAP_US};
+
+pub struct JitterBuffer {
+    latency_budget: u64,
+    base_transit: Option<i64>,
+    last_transit: Option<i64>,
+    last_arrival: u64,
+    jitter: f64,
+    queue: VecDeque<(u64, ScrollEvent)>,
+}
+
+impl JitterBuffer {
+    pub fn new(latency_budget_ms: u64) -> Self {
+        Self {
+            latency_budget: latency_budget_ms * 1000,
+            base_transit: None,
+            last_transit: None,
+            last_arrival: 0,
+            jitter: 0.0,
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, event: ScrollEvent, arrival: u64) -> bool {
+        if self.latency_budget == 0 || event.time_usec == 0 {
+            self.queue.push_back((arrival, event));
+            return true;
+        }
+
+        let transit = arrival as i64 - event.time_usec as i64;
+
+        let new_gesture = self.queue.is_empty()
+            && arrival.saturating_sub(self.last_arrival) > JITTER_GAP_US;
+        if new_gesture {
+            self.base_transit = None;
+            self.last_transit = None;
+        }
+        self.last_arrival = arrival;
+
+        if let Some(last) = self.last_transit {
+            let delta = (transit - last).abs() as f64;
+            self.jitter += (delta - self.jitter) / 16.0;
+        }
+        self.last_transit = Some(transit);
+
+        let base = match self.base_transit {
+            Some(base) if base <= transit => base,
+            _ => {
+                self.base_transit = Some(transit);
+                transit
+            }
+        };
+
+        let late = (transit - base) as u64;
+        if late > self.latency_budget && !event.stop {
+            trace!(
+                late_us = late,
+                budget_us = self.latency_budget,
+                "Dropping late scroll event",
+            );
+            return false;
+        }
+
+        let delay = ((self.jitter * 2.0) as u64)
+            .max(JITTER_MIN_DELAY_US)
+            .min(self.latency_budget);
+        let playout = (event.time_usec as i64 + base) as u64 + delay;
+        let playout = playout.max(arrival);
+
+        let index = self.queue.iter()
+            .position(|(t, _)| *t > playout)
+            .unwrap_or(self.queue.len());
+        self.queue.insert(index, (playout, event));
+
+        true
+    }
+
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.queue.front().map(|(t, _)| *t)
+    }
+
+    pub fn pop_due(&mut self, now: u64) -> Option<ScrollEvent> {
+        match self.queue.front() {
+            Some((t, _)) if *t <= now => self.queue.pop_front().map(|(_, e)| e),
+            _ => None,
+        }
+    }
+}
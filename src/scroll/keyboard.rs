@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use crate::errors::{Error, ErrorKind, Result, trace};
+
+use super::event::InputEvent;
+use super::utils::SharedUinput;
+use super::constants::{
+    EVIOCGRAB,
+    EVIOCSCLOCKID,
+    POLL_TIMEOUT,
+    EV_SYN,
+    EV_KEY,
+    SYN_REPORT,
+    KEY_MAX,
+};
+
+const EVENT_SIZE: usize = mem::size_of::<InputEvent>();
+const READ_BATCH: usize = 64;
+const KEY_BITS_LEN: usize = KEY_MAX.div_ceil(8) + 1;
+
+const fn eviocgkey(len: u32) -> libc::c_ulong {
+    (2u64 << 30) | (((len as u64) & 0x3fff) << 16) | (0x45u64 << 8) | 0x18
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct KeyInput {
+    pub code: u16,
+    pub value: i32,
+    pub time_usec: u64,
+}
+
+pub struct KeyboardCapture {
+    device: File,
+    grabbed: bool,
+    pressed: HashSet<u16>,
+}
+
+impl KeyboardCapture {
+    pub fn new(device_path: impl AsRef<Path>) -> Result<Self> {
+        let path = device_path.as_ref();
+
+        let device = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .map_err(|e| Error::wrap(e, ErrorKind::Read)
+                .with_msg("scroll: Failed to open keyboard device")
+                .with_ctx("path", path.display()))?;
+
+        let clock = libc::CLOCK_MONOTONIC;
+        unsafe { libc::ioctl(device.as_raw_fd(), EVIOCSCLOCKID, &clock) };
+
+        Ok(Self {
+            device,
+            grabbed: false,
+            pressed: HashSet::new(),
+        })
+    }
+
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    pub fn pressed(&self) -> &HashSet<u16> {
+        &self.pressed
+    }
+
+    pub fn keys_held(&self) -> bool {
+        let mut key_bits = [0u8; KEY_BITS_LEN];
+        let ret = unsafe {
+            libc::ioctl(
+                self.device.as_raw_fd(),
+                eviocgkey(key_bits.len() as u32),
+                key_bits.as_mut_ptr(),
+            )
+        };
+
+        ret >= 0 && key_bits.iter().any(|b| *b != 0)
+    }
+
+    pub fn set_grab(&mut self, grab: bool) -> Result<()> {
+        if self.grabbed == grab {
+            return Ok(());
+        }
+
+        let fd = self.device.as_raw_fd();
+        if unsafe { libc::ioctl(fd, EVIOCGRAB, grab as libc::c_int) } != 0 {
+            return Err(Error::wrap(std::io::Error::last_os_error(), ErrorKind::Exec)
+                .with_msg("scroll: Failed to change keyboard device grab")
+                .with_ctx("grab", grab));
+        }
+
+        trace!(grab = grab, "Keyboard device grab changed");
+        self.grabbed = grab;
+
+        Ok(())
+    }
+
+    pub fn read_keys(&mut self) -> Result<Vec<KeyInput>> {
+        let mut pfd = libc::pollfd {
+            fd: self.device.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        let ret = unsafe { libc::poll(&mut pfd, 1, POLL_TIMEOUT) };
+        if ret < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            return Err(Error::wrap(err, ErrorKind::Read)
+                .with_msg("scroll: Poll failed"));
+        }
+
+        if ret == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut buf = [0u8; READ_BATCH * EVENT_SIZE];
+        let len = match self.device.read(&mut buf) {
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(Error::wrap(e, ErrorKind::Read)
+                    .with_msg("scroll: Failed to read keyboard events"));
+            }
+        };
+
+        let mut keys = Vec::new();
+        for chunk in buf[..len].chunks_exact(EVENT_SIZE) {
+            let event: InputEvent = unsafe {
+                std::ptr::read_unaligned(chunk.as_ptr() as *const InputEvent)
+            };
+            if event.type_ != EV_KEY as u16 {
+                continue;
+            }
+
+            match event.value {
+                0 => self.pressed.remove(&event.code),
+                _ => self.pressed.insert(event.code),
+            };
+            keys.push(KeyInput {
+                code: event.code,
+                value: event.value,
+                time_usec: event.tv_sec as u64 * 1_000_000 + event.tv_usec as u64,
+            });
+        }
+
+        Ok(keys)
+    }
+}
+
+impl Drop for KeyboardCapture {
+    fn drop(&mut self) {
+        if self.grabbed {
+            let fd = self.device.as_raw_fd();
+            unsafe { libc::ioctl(fd, EVIOCGRAB, 0) };
+        }
+    }
+}
+
+pub struct KeySender {
+    uinput: SharedUinput,
+}
+
+impl KeySender {
+    pub fn new(uinput: SharedUinput) -> Self {
+        Self {
+            uinput,
+        }
+    }
+
+    pub fn send(&self, code: u16, value: i32) -> Result<()> {
+        if code as usize > KEY_MAX {
+            return Ok(());
+        }
+
+        let mut buf = Vec::with_capacity(2 * EVENT_SIZE);
+        for (type_, code, value) in [(EV_KEY as u16, code, value), (EV_SYN, SYN_REPORT, 0)] {
+            let event = InputEvent {
+                type_,
+                code,
+                value,
+                ..Default::default()
+            };
+            let bytes: [u8; EVENT_SIZE] = unsafe { mem::transmute(event) };
+            buf.extend_from_slice(&bytes);
+        }
+
+        self.uinput.write_raw(&buf)
+    }
+}
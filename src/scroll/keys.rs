@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use crate::errors::{Result, Error, ErrorKind};
+
+const KEY_NAMES: &[(&str, u16)] = &[
+    ("esc", 1),
+    ("1", 2), ("2", 3), ("3", 4), ("4", 5), ("5", 6),
+    ("6", 7), ("7", 8), ("8", 9), ("9", 10), ("0", 11),
+    ("minus", 12), ("equal", 13), ("backspace", 14), ("tab", 15),
+    ("q", 16), ("w", 17), ("e", 18), ("r", 19), ("t", 20),
+    ("y", 21), ("u", 22), ("i", 23), ("o", 24), ("p", 25),
+    ("leftbrace", 26), ("rightbrace", 27), ("enter", 28), ("leftctrl", 29),
+    ("a", 30), ("s", 31), ("d", 32), ("f", 33), ("g", 34),
+    ("h", 35), ("j", 36), ("k", 37), ("l", 38),
+    ("semicolon", 39), ("apostrophe", 40), ("grave", 41), ("leftshift", 42),
+    ("backslash", 43),
+    ("z", 44), ("x", 45), ("c", 46), ("v", 47), ("b", 48), ("n", 49), ("m", 50),
+    ("comma", 51), ("dot", 52), ("slash", 53), ("rightshift", 54),
+    ("leftalt", 56), ("space", 57), ("capslock", 58),
+    ("f1", 59), ("f2", 60), ("f3", 61), ("f4", 62), ("f5", 63),
+    ("f6", 64), ("f7", 65), ("f8", 66), ("f9", 67), ("f10", 68),
+    ("numlock", 69), ("scrolllock", 70), ("f11", 87), ("f12", 88),
+    ("rightctrl", 97), ("sysrq", 99), ("rightalt", 100),
+    ("home", 102), ("up", 103), ("pageup", 104), ("left", 105), ("right", 106),
+    ("end", 107), ("down", 108), ("pagedown", 109), ("insert", 110), ("delete", 111),
+    ("pause", 119), ("leftmeta", 125), ("rightmeta", 126), ("compose", 127),
+];
+
+const MODIFIER_NAMES: &[(&str, &[u16])] = &[
+    ("ctrl", &[29, 97]),
+    ("shift", &[42, 54]),
+    ("alt", &[56, 100]),
+    ("super", &[125, 126]),
+    ("meta", &[125, 126]),
+];
+
+pub fn parse_key(name: &str) -> Option<u16> {
+    let name = name.trim().to_ascii_lowercase();
+    let name = name.strip_prefix("key_").unwrap_or(&name);
+
+    if let Some((_, code)) = KEY_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(*code);
+    }
+
+    name.parse().ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyChord {
+    keys: Vec<Vec<u16>>,
+}
+
+impl KeyChord {
+    pub fn parse(chord: &str) -> Result<Self> {
+        let mut keys = Vec::new();
+
+        for part in chord.split('+') {
+            let name = part.trim().to_ascii_lowercase();
+            if let Some((_, codes)) = MODIFIER_NAMES.iter().find(|(n, _)| *n == name) {
+                keys.push(codes.to_vec());
+                continue;
+            }
+
+            let code = parse_key(&name).ok_or_else(|| Error::new(ErrorKind::Parse)
+                .with_msg("scroll: Unknown key name in hotkey")
+                .with_ctx("hotkey", chord)
+                .with_ctx("key", part))?;
+            keys.push(vec![code]);
+        }
+
+        if keys.is_empty() {
+            return Err(Error::new(ErrorKind::Parse)
+                .with_msg("scroll: Empty hotkey")
+                .with_ctx("hotkey", chord));
+        }
+
+        Ok(Self { keys })
+    }
+
+    pub fn codes(&self) -> Vec<u16> {
+        self.keys.iter()
+            .filter_map(|codes| codes.first().copied())
+            .collect()
+    }
+
+    pub fn matches(&self, pressed: &HashSet<u16>, key: u16) -> bool {
+        self.keys.last().is_some_and(|last| last.contains(&key))
+            && self.keys.iter()
+                .all(|codes| codes.iter().any(|c| pressed.contains(c)))
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+
+use crate::errors::trace;
+use crate::synq::{ScrollEvent, ScrollSource};
+
+use super::constants::{KINETIC_INTERVAL_US, KINETIC_WINDOW_US};
+
+pub struct KineticScroller {
+    decay_us: f64,
+    min_velocity: f64,
+    samples: VecDeque<(u64, f64, f64)>,
+    velocity: Option<(f64, f64)>,
+    next_tick: u64,
+}
+
+impl KineticScroller {
+    pub fn new(decay_ms: u64, min_velocity: f64) -> Self {
+        Self {
+            decay_us: (decay_ms.max(1) * 1000) as f64,
+            min_velocity,
+            samples: VecDeque::new(),
+            velocity: None,
+            next_tick: 0,
+        }
+    }
+
+    // Scroll was dropped because the pointer left, nothing may coast on
+    pub fn cancel(&mut self) {
+        self.samples.clear();
+        if self.velocity.take().is_some() {
+            trace!("Kinetic scroll cancelled");
+        }
+    }
+
+    pub fn track(&mut self, event: &ScrollEvent, now: u64) {
+        if self.velocity.take().is_some() {
+            trace!("Kinetic scroll interrupted");
+        }
+
+        if event.source() == ScrollSource::Wheel {
+            self.samples.clear();
+            return;
+        }
+
+        if event.delta_x != 0.0 || event.delta_y != 0.0 {
+            self.samples.push_back((now, event.delta_x, event.delta_y));
+        }
+        while self.samples.front().is_some_and(|(t, _, _)| now - t > KINETIC_WINDOW_US) {
+            self.samples.pop_front();
+        }
+
+        if event.stop {
+            self.start(now);
+        }
+    }
+
+    fn start(&mut self, now: u64) {
+        let Some((first, _, _)) = self.samples.front().copied() else {
+            return;
+        };
+
+        let span = (now - first).max(KINETIC_INTERVAL_US) as f64 / 1_000_000.0;
+        let (dx, dy) = self.samples.drain(..)
+            .fold((0.0, 0.0), |(x, y), (_, dx, dy)| (x + dx, y + dy));
+        let velocity = (dx / span, dy / span);
+
+        if velocity.0.hypot(velocity.1) < self.min_velocity {
+            return;
+        }
+
+        trace!(velocity_x = velocity.0, velocity_y = velocity.1, "Kinetic scroll started");
+        self.velocity = Some(velocity);
+        self.next_tick = now + KINETIC_INTERVAL_US;
+    }
+
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.velocity.map(|_| self.next_tick)
+    }
+
+    pub fn pop_due(&mut self, now: u64) -> Option<(f64, f64)> {
+        let (vx, vy) = self.velocity?;
+        if now < self.next_tick {
+            return None;
+        }
+
+        let dt = KINETIC_INTERVAL_US as f64;
+        let factor = (-dt / self.decay_us).exp();
+        let (vx, vy) = (vx * factor, vy * factor);
+        self.next_tick += KINETIC_INTERVAL_US;
+
+        if vx.hypot(vy) < self.min_velocity {
+            trace!("Kinetic scroll stopped");
+            self.velocity = None;
+            return None;
+        }
+
+        self.velocity = Some((vx, vy));
+        let seconds = dt / 1_000_000.0;
+        Some((vx * seconds, vy * seconds))
+    }
+}
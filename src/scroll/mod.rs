@@ -1,15 +1,34 @@
 #[allow(clippy::module_inception)]
 mod scroll;
 mod utils;
-pub use utils::SharedUinput;
+pub use utils::{SharedUinput, UinputCapabilities};
 mod constants;
 pub use constants::*;
 mod device;
 pub use device::*;
 mod event;
+pub use event::{GestureInput, ReceiverEvent};
+mod activation;
+pub use activation::*;
 mod blocker;
 pub use blocker::*;
 mod receiver;
 pub use receiver::*;
 mod sender;
 pub use sender::*;
+mod jitter;
+pub use jitter::*;
+mod kinetic;
+pub use kinetic::*;
+mod transform;
+pub use transform::*;
+mod generator;
+pub use generator::*;
+mod keys;
+pub use keys::*;
+mod pointer;
+pub use pointer::*;
+mod keyboard;
+pub use keyboard::*;
+mod touchpad;
+pub use touchpad::*;
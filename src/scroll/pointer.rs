@@ -0,0 +1,329 @@
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use crate::errors::{Error, ErrorKind, Result, trace};
+
+use super::event::InputEvent;
+use super::utils::{SharedUinput, abs_resolution};
+use super::constants::{
+    EVIOCGRAB,
+    EVIOCSCLOCKID,
+    POLL_TIMEOUT,
+    EV_SYN,
+    EV_KEY,
+    EV_REL,
+    EV_ABS,
+    SYN_REPORT,
+    ABS_X,
+    ABS_Y,
+    BTN_TOUCH,
+    POINTER_COUNTS_PER_MM,
+    REL_X,
+    REL_Y,
+    REL_WHEEL,
+    REL_HWHEEL,
+    REL_WHEEL_HI_RES,
+    REL_HWHEEL_HI_RES,
+    BTN_LEFT,
+    BTN_TASK,
+};
+
+const EVENT_SIZE: usize = mem::size_of::<InputEvent>();
+const READ_BATCH: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub enum PointerInput {
+    Motion { dx: i32, dy: i32, time_usec: u64 },
+    Button { code: u16, pressed: bool, time_usec: u64 },
+    Scroll { v120_x: i32, v120_y: i32, time_usec: u64 },
+}
+
+pub struct PointerCapture {
+    device: File,
+    grabbed: bool,
+    hi_res: bool,
+    dx: i32,
+    dy: i32,
+    v120_x: i32,
+    v120_y: i32,
+    touchpad: TouchpadMotion,
+}
+
+// Turns absolute touchpad positions into relative motion, starting over
+// each time the finger lifts
+#[derive(Default)]
+struct TouchpadMotion {
+    scale: (f64, f64),
+    position: (Option<i32>, Option<i32>),
+    last: Option<(i32, i32)>,
+    remainder: (f64, f64),
+}
+
+impl TouchpadMotion {
+    fn new(fd: libc::c_int) -> Self {
+        let scale = |code| POINTER_COUNTS_PER_MM / abs_resolution(fd, code).unwrap_or(1) as f64;
+
+        Self {
+            scale: (scale(ABS_X), scale(ABS_Y)),
+            ..Default::default()
+        }
+    }
+
+    fn reset(&mut self) {
+        self.position = (None, None);
+        self.last = None;
+        self.remainder = (0.0, 0.0);
+    }
+
+    fn motion(&mut self) -> (i32, i32) {
+        let (Some(x), Some(y)) = self.position else {
+            return (0, 0);
+        };
+        let Some((last_x, last_y)) = self.last.replace((x, y)) else {
+            return (0, 0);
+        };
+
+        let exact_x = (x - last_x) as f64 * self.scale.0 + self.remainder.0;
+        let exact_y = (y - last_y) as f64 * self.scale.1 + self.remainder.1;
+        let (dx, dy) = (exact_x.trunc(), exact_y.trunc());
+        self.remainder = (exact_x - dx, exact_y - dy);
+
+        (dx as i32, dy as i32)
+    }
+}
+
+impl PointerCapture {
+    pub fn new(device_path: impl AsRef<Path>) -> Result<Self> {
+        let path = device_path.as_ref();
+
+        let device = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .map_err(|e| Error::wrap(e, ErrorKind::Read)
+                .with_msg("scroll: Failed to open pointer device")
+                .with_ctx("path", path.display()))?;
+
+        let clock = libc::CLOCK_MONOTONIC;
+        unsafe { libc::ioctl(device.as_raw_fd(), EVIOCSCLOCKID, &clock) };
+        let touchpad = TouchpadMotion::new(device.as_raw_fd());
+
+        Ok(Self {
+            device,
+            touchpad,
+            grabbed: false,
+            hi_res: false,
+            dx: 0,
+            dy: 0,
+            v120_x: 0,
+            v120_y: 0,
+        })
+    }
+
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    pub fn set_grab(&mut self, grab: bool) -> Result<()> {
+        if self.grabbed == grab {
+            return Ok(());
+        }
+
+        let fd = self.device.as_raw_fd();
+        if unsafe { libc::ioctl(fd, EVIOCGRAB, grab as libc::c_int) } != 0 {
+            return Err(Error::wrap(std::io::Error::last_os_error(), ErrorKind::Exec)
+                .with_msg("scroll: Failed to change pointer device grab")
+                .with_ctx("grab", grab));
+        }
+
+        trace!(grab = grab, "Pointer device grab changed");
+        self.grabbed = grab;
+        self.dx = 0;
+        self.dy = 0;
+        self.v120_x = 0;
+        self.v120_y = 0;
+        self.touchpad.reset();
+
+        Ok(())
+    }
+
+    pub fn read_inputs(&mut self) -> Result<Vec<PointerInput>> {
+        let mut pfd = libc::pollfd {
+            fd: self.device.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        let ret = unsafe { libc::poll(&mut pfd, 1, POLL_TIMEOUT) };
+        if ret < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            return Err(Error::wrap(err, ErrorKind::Read)
+                .with_msg("scroll: Poll failed"));
+        }
+
+        if ret == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut buf = [0u8; READ_BATCH * EVENT_SIZE];
+        let len = match self.device.read(&mut buf) {
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(Error::wrap(e, ErrorKind::Read)
+                    .with_msg("scroll: Failed to read pointer events"));
+            }
+        };
+
+        let mut inputs = Vec::new();
+        for chunk in buf[..len].chunks_exact(EVENT_SIZE) {
+            let event: InputEvent = unsafe {
+                std::ptr::read_unaligned(chunk.as_ptr() as *const InputEvent)
+            };
+            self.process(&event, &mut inputs);
+        }
+
+        Ok(inputs)
+    }
+
+    fn process(&mut self, event: &InputEvent, inputs: &mut Vec<PointerInput>) {
+        let time_usec = event.tv_sec as u64 * 1_000_000 + event.tv_usec as u64;
+
+        match event.type_ {
+            t if t == EV_REL as u16 => match event.code {
+                REL_X => self.dx += event.value,
+                REL_Y => self.dy += event.value,
+                REL_WHEEL_HI_RES => {
+                    self.hi_res = true;
+                    self.v120_y += event.value;
+                }
+                REL_HWHEEL_HI_RES => {
+                    self.hi_res = true;
+                    self.v120_x += event.value;
+                }
+                REL_WHEEL if !self.hi_res => self.v120_y += event.value * 120,
+                REL_HWHEEL if !self.hi_res => self.v120_x += event.value * 120,
+                _ => {}
+            },
+            t if t == EV_ABS as u16 => match event.code {
+                ABS_X => self.touchpad.position.0 = Some(event.value),
+                ABS_Y => self.touchpad.position.1 = Some(event.value),
+                _ => {}
+            },
+            t if t == EV_KEY as u16 && event.code == BTN_TOUCH && event.value == 0 => {
+                self.touchpad.reset();
+            }
+            t if t == EV_KEY as u16
+                && (BTN_LEFT..=BTN_TASK).contains(&event.code)
+                && event.value != 2 =>
+            {
+                inputs.push(PointerInput::Button {
+                    code: event.code,
+                    pressed: event.value == 1,
+                    time_usec,
+                });
+            }
+            EV_SYN if event.code == SYN_REPORT => {
+                let (dx, dy) = self.touchpad.motion();
+                self.dx += dx;
+                self.dy += dy;
+
+                if self.dx != 0 || self.dy != 0 {
+                    inputs.push(PointerInput::Motion {
+                        dx: self.dx,
+                        dy: self.dy,
+                        time_usec,
+                    });
+                }
+                if self.v120_x != 0 || self.v120_y != 0 {
+                    inputs.push(PointerInput::Scroll {
+                        v120_x: self.v120_x,
+                        v120_y: self.v120_y,
+                        time_usec,
+                    });
+                }
+                self.dx = 0;
+                self.dy = 0;
+                self.v120_x = 0;
+                self.v120_y = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for PointerCapture {
+    fn drop(&mut self) {
+        if self.grabbed {
+            let fd = self.device.as_raw_fd();
+            unsafe { libc::ioctl(fd, EVIOCGRAB, 0) };
+        }
+    }
+}
+
+pub struct PointerSender {
+    uinput: SharedUinput,
+}
+
+impl PointerSender {
+    pub fn new(uinput: SharedUinput) -> Self {
+        Self {
+            uinput,
+        }
+    }
+
+    pub fn wheel(&self, clicks: i32) -> Result<()> {
+        if clicks == 0 {
+            return Ok(());
+        }
+
+        self.write(vec![
+            (EV_REL as u16, REL_WHEEL_HI_RES, clicks * 120),
+            (EV_REL as u16, REL_WHEEL, clicks),
+            (EV_SYN, SYN_REPORT, 0),
+        ])
+    }
+
+    pub fn send(&self, dx: i32, dy: i32, button: u16, pressed: bool) -> Result<()> {
+        let mut events = Vec::with_capacity(3);
+
+        if dx != 0 {
+            events.push((EV_REL as u16, REL_X, dx));
+        }
+        if dy != 0 {
+            events.push((EV_REL as u16, REL_Y, dy));
+        }
+        if (BTN_LEFT..=BTN_TASK).contains(&button) {
+            events.push((EV_KEY as u16, button, pressed as i32));
+        }
+        if events.is_empty() {
+            return Ok(());
+        }
+        events.push((EV_SYN, SYN_REPORT, 0));
+
+        self.write(events)
+    }
+
+    fn write(&self, events: Vec<(u16, u16, i32)>) -> Result<()> {
+        let mut buf = Vec::with_capacity(events.len() * EVENT_SIZE);
+        for (type_, code, value) in events {
+            let event = InputEvent {
+                type_,
+                code,
+                value,
+                ..Default::default()
+            };
+            let bytes: [u8; EVENT_SIZE] = unsafe { mem::transmute(event) };
+            buf.extend_from_slice(&bytes);
+        }
+
+        self.uinput.write_raw(&buf)
+    }
+}
@@ -3,15 +3,29 @@ use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::io::{AsRawFd, OwnedFd};
 use std::path::Path;
 
-use input::event::pointer::{Axis, PointerEvent, PointerScrollEvent};
+use input::event::pointer::{Axis, PointerEvent, PointerEventTrait, PointerScrollEvent};
+use input::event::gesture::{
+    GestureEvent,
+    GestureEventTrait,
+    GestureEventCoordinates,
+    GestureEndEvent,
+    GesturePinchEvent,
+    GesturePinchEventTrait,
+    GestureSwipeEvent,
+    GestureHoldEvent,
+};
 use input::{Event, Libinput, LibinputInterface};
 use libc::{O_RDONLY, O_RDWR, O_WRONLY, O_ACCMODE};
 use crate::errors::trace;
 
+use crate::config::GestureKind;
 use crate::errors::{Error, ErrorKind, Result};
-use super::event::ScrollEvent;
+use super::event::{ScrollEvent, GestureInput, ReceiverEvent};
 use super::constants::{
     ScrollSource,
+    GesturePhase,
+    POLL_TIMEOUT,
+    V120_PER_DELTA,
 };
 
 struct Interface;
@@ -60,7 +74,11 @@ impl ScrollReceiver {
         })
     }
 
-    pub fn read_event(&mut self) -> Result<Option<ScrollEvent>> {
+    pub fn read_event(&mut self) -> Result<Option<ReceiverEvent>> {
+        if let Some(event) = self.next_event() {
+            return Ok(Some(event));
+        }
+
         let mut pfd = libc::pollfd {
             fd: self.libinput.as_raw_fd(),
             events: libc::POLLIN,
@@ -68,101 +86,188 @@ impl ScrollReceiver {
         };
 
         let ret = unsafe {
-            libc::poll(&mut pfd, 1, -1)
+            libc::poll(&mut pfd, 1, POLL_TIMEOUT)
         };
         if ret < 0 {
-            return Err(Error::wrap(std::io::Error::last_os_error(), ErrorKind::Read)
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                return Ok(None);
+            }
+            return Err(Error::wrap(err, ErrorKind::Read)
                 .with_msg("scroll: Poll failed"));
         }
 
+        if ret == 0 {
+            return Ok(None);
+        }
+
         self.libinput.dispatch().map_err(|e| {
             Error::wrap(e, ErrorKind::Read)
                 .with_msg("scroll: Failed to dispatch libinput events")
         })?;
 
+        Ok(self.next_event())
+    }
+
+    fn next_event(&mut self) -> Option<ReceiverEvent> {
         for event in &mut self.libinput {
-            if let Event::Pointer(pointer_event) = event {
-                match pointer_event {
-                    PointerEvent::ScrollWheel(wheel_event) => {
-                        let delta_x = if wheel_event.has_axis(Axis::Horizontal) {
-                            wheel_event.scroll_value(Axis::Horizontal)
-                        } else {
-                            0.0
-                        };
-                        let delta_y = if wheel_event.has_axis(Axis::Vertical) {
-                            wheel_event.scroll_value(Axis::Vertical)
-                        } else {
-                            0.0
-                        };
+            match event {
+                Event::Pointer(pointer_event) => {
+                    match pointer_event {
+                        PointerEvent::ScrollWheel(wheel_event) => {
+                            let delta_x = if wheel_event.has_axis(Axis::Horizontal) {
+                                wheel_event.scroll_value_v120(Axis::Horizontal) / V120_PER_DELTA
+                            } else {
+                                0.0
+                            };
+                            let delta_y = if wheel_event.has_axis(Axis::Vertical) {
+                                wheel_event.scroll_value_v120(Axis::Vertical) / V120_PER_DELTA
+                            } else {
+                                0.0
+                            };
 
-                        trace!(
-                            source = ?ScrollSource::Wheel,
-                            delta_x = delta_x,
-                            delta_y = delta_y,
-                            "Scroll event"
-                        );
+                            trace!(
+                                source = ?ScrollSource::Wheel,
+                                delta_x = delta_x,
+                                delta_y = delta_y,
+                                "Scroll event"
+                            );
 
-                        return Ok(Some(ScrollEvent {
-                            source: ScrollSource::Wheel,
-                            delta_x,
-                            delta_y,
-                        }));
-                    }
-                    PointerEvent::ScrollFinger(finger_event) => {
-                        let delta_x = if finger_event.has_axis(Axis::Horizontal) {
-                            finger_event.scroll_value(Axis::Horizontal)
-                        } else {
-                            0.0
-                        };
-                        let delta_y = if finger_event.has_axis(Axis::Vertical) {
-                            finger_event.scroll_value(Axis::Vertical)
-                        } else {
-                            0.0
-                        };
+                            return Some(ReceiverEvent::Scroll(ScrollEvent {
+                                source: ScrollSource::Wheel,
+                                delta_x,
+                                delta_y,
+                                time_usec: wheel_event.time_usec(),
+                                stop: false,
+                            }));
+                        }
+                        PointerEvent::ScrollFinger(finger_event) => {
+                            let delta_x = if finger_event.has_axis(Axis::Horizontal) {
+                                finger_event.scroll_value(Axis::Horizontal)
+                            } else {
+                                0.0
+                            };
+                            let delta_y = if finger_event.has_axis(Axis::Vertical) {
+                                finger_event.scroll_value(Axis::Vertical)
+                            } else {
+                                0.0
+                            };
 
-                        trace!(
-                            source = ?ScrollSource::Finger,
-                            delta_x = delta_x,
-                            delta_y = delta_y,
-                            "Scroll event",
-                        );
+                            trace!(
+                                source = ?ScrollSource::Finger,
+                                delta_x = delta_x,
+                                delta_y = delta_y,
+                                "Scroll event",
+                            );
 
-                        return Ok(Some(ScrollEvent {
-                            source: ScrollSource::Finger,
-                            delta_x,
-                            delta_y,
-                        }));
-                    }
-                    PointerEvent::ScrollContinuous(continuous_event) => {
-                        let delta_x = if continuous_event.has_axis(Axis::Horizontal) {
-                            continuous_event.scroll_value(Axis::Horizontal)
-                        } else {
-                            0.0
-                        };
-                        let delta_y = if continuous_event.has_axis(Axis::Vertical) {
-                            continuous_event.scroll_value(Axis::Vertical)
-                        } else {
-                            0.0
-                        };
+                            return Some(ReceiverEvent::Scroll(ScrollEvent {
+                                source: ScrollSource::Finger,
+                                delta_x,
+                                delta_y,
+                                time_usec: finger_event.time_usec(),
+                                stop: delta_x == 0.0 && delta_y == 0.0,
+                            }));
+                        }
+                        PointerEvent::ScrollContinuous(continuous_event) => {
+                            let delta_x = if continuous_event.has_axis(Axis::Horizontal) {
+                                continuous_event.scroll_value(Axis::Horizontal)
+                            } else {
+                                0.0
+                            };
+                            let delta_y = if continuous_event.has_axis(Axis::Vertical) {
+                                continuous_event.scroll_value(Axis::Vertical)
+                            } else {
+                                0.0
+                            };
 
+                            trace!(
+                                source = ?ScrollSource::Continuous,
+                                delta_x = delta_x,
+                                delta_y = delta_y,
+                                "Scroll event"
+                            );
+
+                            return Some(ReceiverEvent::Scroll(ScrollEvent {
+                                source: ScrollSource::Continuous,
+                                delta_x,
+                                delta_y,
+                                time_usec: continuous_event.time_usec(),
+                                stop: delta_x == 0.0 && delta_y == 0.0,
+                            }));
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Gesture(gesture_event) => {
+                    if let Some(gesture) = gesture_input(gesture_event) {
                         trace!(
-                            source = ?ScrollSource::Continuous,
-                            delta_x = delta_x,
-                            delta_y = delta_y,
-                            "Scroll event"
+                            kind = ?gesture.kind,
+                            phase = ?gesture.phase,
+                            fingers = gesture.fingers,
+                            "Gesture event",
                         );
-
-                        return Ok(Some(ScrollEvent {
-                            source: ScrollSource::Continuous,
-                            delta_x,
-                            delta_y,
-                        }));
+                        return Some(ReceiverEvent::Gesture(gesture));
                     }
-                    _ => {}
                 }
+                _ => {}
             }
         }
 
-        Ok(None)
+        None
     }
 }
+
+fn gesture_input(event: GestureEvent) -> Option<GestureInput> {
+    let input = |kind, phase, event: &dyn GestureEventTrait| GestureInput {
+        kind,
+        phase,
+        fingers: event.finger_count().max(0) as u32,
+        dx: 0.0,
+        dy: 0.0,
+        scale: 1.0,
+        angle_delta: 0.0,
+        time_usec: event.time_usec(),
+    };
+    let end_phase = |cancelled: bool| if cancelled {
+        GesturePhase::Cancel
+    } else {
+        GesturePhase::End
+    };
+
+    let gesture = match event {
+        GestureEvent::Swipe(GestureSwipeEvent::Begin(e)) => {
+            input(GestureKind::Swipe, GesturePhase::Begin, &e)
+        }
+        GestureEvent::Swipe(GestureSwipeEvent::Update(e)) => GestureInput {
+            dx: e.dx_unaccelerated(),
+            dy: e.dy_unaccelerated(),
+            ..input(GestureKind::Swipe, GesturePhase::Update, &e)
+        },
+        GestureEvent::Swipe(GestureSwipeEvent::End(e)) => {
+            input(GestureKind::Swipe, end_phase(e.cancelled()), &e)
+        }
+        GestureEvent::Pinch(GesturePinchEvent::Begin(e)) => {
+            input(GestureKind::Pinch, GesturePhase::Begin, &e)
+        }
+        GestureEvent::Pinch(GesturePinchEvent::Update(e)) => GestureInput {
+            dx: e.dx_unaccelerated(),
+            dy: e.dy_unaccelerated(),
+            scale: e.scale(),
+            angle_delta: e.angle_delta(),
+            ..input(GestureKind::Pinch, GesturePhase::Update, &e)
+        },
+        GestureEvent::Pinch(GesturePinchEvent::End(e)) => GestureInput {
+            scale: e.scale(),
+            ..input(GestureKind::Pinch, end_phase(e.cancelled()), &e)
+        },
+        GestureEvent::Hold(GestureHoldEvent::Begin(e)) => {
+            input(GestureKind::Hold, GesturePhase::Begin, &e)
+        }
+        GestureEvent::Hold(GestureHoldEvent::End(e)) => {
+            input(GestureKind::Hold, end_phase(e.cancelled()), &e)
+        }
+        _ => return None,
+    };
+
+    Some(gesture)
+}
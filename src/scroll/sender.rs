@@ -2,12 +2,15 @@ use std::mem;
 use std::ptr;
 
 use crate::errors::{Result, trace};
+use crate::config::ScrollOutput;
 use crate::transport::{Transport};
 use crate::utils;
 
 use super::event::InputEvent;
 use super::utils::SharedUinput;
+use super::transform::ScrollPipeline;
 use super::constants::{
+    ScrollSource,
     EV_SYN,
     EV_REL,
     REL_WHEEL,
@@ -15,6 +18,8 @@ use super::constants::{
     REL_WHEEL_HI_RES,
     REL_HWHEEL_HI_RES,
     SYN_REPORT,
+    V120_PER_CLICK,
+    V120_PER_DELTA,
     SCROLL_TTL,
     BLUR_TTL,
 };
@@ -24,93 +29,133 @@ const EVENT_SIZE: usize = mem::size_of::<InputEvent>();
 pub struct ScrollSender {
     uinput: SharedUinput,
     transport: Transport,
+    pipeline: ScrollPipeline,
+    output: ScrollOutput,
+    hi_res_remainder: (f64, f64),
+    discrete_remainder: (i32, i32),
 }
 
 impl ScrollSender {
-    pub fn new(uinput: SharedUinput, transport: Transport) -> Self {
+    pub fn new(
+        uinput: SharedUinput,
+        transport: Transport,
+        pipeline: ScrollPipeline,
+        output: ScrollOutput,
+    ) -> Self {
         Self {
             uinput,
             transport,
+            pipeline,
+            output,
+            hi_res_remainder: (0.0, 0.0),
+            discrete_remainder: (0, 0),
         }
     }
 
-    pub fn send(&mut self, delta_x: f64, delta_y: f64) -> Result<()> {
+    // Local playback injects on this host, no peer drives it so there is no
+    // active state to gate on
+    pub fn send(&mut self, source: ScrollSource, delta_x: f64, delta_y: f64) -> Result<()> {
+        let (delta_x, delta_y) = self.transform(source, delta_x, delta_y);
+        self.emit(delta_x, delta_y)
+    }
+
+    pub fn transform(&self, source: ScrollSource, delta_x: f64, delta_y: f64) -> (f64, f64) {
+        self.pipeline.apply(source, delta_x, delta_y)
+    }
+
+    // Returns whether the scroll was injected, dropped scroll must not feed
+    // anything derived from it such as kinetic motion
+    pub fn send_transformed(&mut self, delta_x: f64, delta_y: f64, mirror: bool) -> Result<bool> {
+        // Dropped events must not touch the remainders, or the next scroll
+        // starts with a stale partial click. Mirrored copies skip the TTL,
+        // the pointer is on another peer and this one never reports scroll
+        if !mirror && (!self.transport.active_state.is_host_active() || !self.check_ttl()) {
+            return Ok(false);
+        }
+
+        self.emit(delta_x, delta_y)?;
+        Ok(true)
+    }
+
+    // Kinetic motion continues after the guest stops reporting scroll, so it
+    // skips the TTL and must not deactivate the guest. It is derived from
+    // already transformed deltas and skips the pipeline too
+    pub fn send_synthesized(&mut self, delta_x: f64, delta_y: f64) -> Result<()> {
+        self.emit(delta_x, delta_y)
+    }
+
+    // Scroll only counts while the guest keeps reporting its own scroll,
+    // once it goes quiet the pointer has left the guest and the host takes over
+    fn check_ttl(&self) -> bool {
+        let now = utils::mono_time_ms();
+        let last_scroll = self.transport.active_state.get_last_scroll();
+
+        if last_scroll > 0 && now - last_scroll > SCROLL_TTL {
+            let last_blur = self.transport.active_state.get_last_blur();
+            if last_blur == 0 {
+                self.transport.active_state.set_last_blur(now);
+            } else if now - last_blur > BLUR_TTL {
+                self.transport.send_deactivate_request();
+                return false;
+            }
+        } else {
+            self.transport.active_state.set_last_blur(0);
+        }
+
+        true
+    }
+
+    fn emit(&mut self, delta_x: f64, delta_y: f64) -> Result<()> {
         let input_now = unsafe {
             let mut tv: libc::timeval = mem::zeroed();
             libc::gettimeofday(&mut tv, ptr::null_mut());
             tv
         };
 
-        // Convert libinput scroll values to v120 units
-        // libinput uses 15 degrees per scroll click by default
-        // v120 uses 120 units per scroll click
-        // So multiply by 120/15 = 8
-        const SCALE: f64 = 8.0;
-
-        let hi_res_y = (delta_y * SCALE) as i32;
-        let hi_res_x = (delta_x * SCALE) as i32;
+        let (hi_res_x, discrete_x) = accumulate(
+            delta_x,
+            &mut self.hi_res_remainder.0,
+            &mut self.discrete_remainder.0,
+        );
+        let (hi_res_y, discrete_y) = accumulate(
+            delta_y,
+            &mut self.hi_res_remainder.1,
+            &mut self.discrete_remainder.1,
+        );
 
         trace!(
             delta_x = delta_x,
             delta_y = delta_y,
             hi_res_x = hi_res_x,
             hi_res_y = hi_res_y,
+            discrete_x = discrete_x,
+            discrete_y = discrete_y,
             "Sending scroll event"
         );
 
-        // Calculate discrete scroll clicks (120 units = 1 click)
-        let discrete_y = hi_res_y / 120;
-        let discrete_x = hi_res_x / 120;
+        let hi_res = self.output != ScrollOutput::Discrete;
+        let discrete = self.output != ScrollOutput::HiRes;
+        let events = [
+            (REL_WHEEL_HI_RES, hi_res_y, hi_res),
+            (REL_HWHEEL_HI_RES, hi_res_x, hi_res),
+            (REL_WHEEL, discrete_y, discrete),
+            (REL_HWHEEL, discrete_x, discrete),
+        ];
 
         let mut buf = [0u8; 5 * EVENT_SIZE];
         let mut offset = 0;
 
-        if hi_res_y != 0 {
-            let event = InputEvent {
-                tv_sec: input_now.tv_sec,
-                tv_usec: input_now.tv_usec,
-                type_: EV_REL as u16,
-                code: REL_WHEEL_HI_RES,
-                value: hi_res_y,
-            };
-            let bytes: [u8; EVENT_SIZE] = unsafe { mem::transmute(event) };
-            buf[offset..offset + EVENT_SIZE].copy_from_slice(&bytes);
-            offset += EVENT_SIZE;
-        }
-
-        if hi_res_x != 0 {
-            let event = InputEvent {
-                tv_sec: input_now.tv_sec,
-                tv_usec: input_now.tv_usec,
-                type_: EV_REL as u16,
-                code: REL_HWHEEL_HI_RES,
-                value: hi_res_x,
-            };
-            let bytes: [u8; EVENT_SIZE] = unsafe { mem::transmute(event) };
-            buf[offset..offset + EVENT_SIZE].copy_from_slice(&bytes);
-            offset += EVENT_SIZE;
-        }
-
-        if discrete_y != 0 {
-            let event = InputEvent {
-                tv_sec: input_now.tv_sec,
-                tv_usec: input_now.tv_usec,
-                type_: EV_REL as u16,
-                code: REL_WHEEL,
-                value: discrete_y,
-            };
-            let bytes: [u8; EVENT_SIZE] = unsafe { mem::transmute(event) };
-            buf[offset..offset + EVENT_SIZE].copy_from_slice(&bytes);
-            offset += EVENT_SIZE;
-        }
+        for (code, value, enabled) in events {
+            if !enabled || value == 0 {
+                continue;
+            }
 
-        if discrete_x != 0 {
             let event = InputEvent {
                 tv_sec: input_now.tv_sec,
                 tv_usec: input_now.tv_usec,
                 type_: EV_REL as u16,
-                code: REL_HWHEEL,
-                value: discrete_x,
+                code,
+                value,
             };
             let bytes: [u8; EVENT_SIZE] = unsafe { mem::transmute(event) };
             buf[offset..offset + EVENT_SIZE].copy_from_slice(&bytes);
@@ -128,23 +173,24 @@ impl ScrollSender {
         buf[offset..offset + EVENT_SIZE].copy_from_slice(&bytes);
         offset += EVENT_SIZE;
 
-        let now = utils::mono_time_ms();
-        let last_scroll = self.transport.active_state.get_last_scroll();
+        self.uinput.write_raw(&buf[..offset])
+    }
+}
 
-        if last_scroll > 0 && now - last_scroll > SCROLL_TTL {
-            let last_blur = self.transport.active_state.get_last_blur();
-            if last_blur == 0 {
-                self.transport.active_state.set_last_blur(now);
-            } else if now - last_blur > BLUR_TTL {
-                if self.transport.active_state.is_host_active() {
-                    self.transport.send_deactivate_request();
-                }
-                return Ok(());
-            }
-        } else {
-            self.transport.active_state.set_last_blur(0);
-        }
+// Converts a libinput scroll delta to whole v120 units, carrying the fraction
+// and any partial click over to the next event so slow scrolls still click
+fn accumulate(delta: f64, hi_res_remainder: &mut f64, discrete_remainder: &mut i32) -> (i32, i32) {
+    let exact = delta * V120_PER_DELTA + *hi_res_remainder;
+    let hi_res = exact.trunc() as i32;
+    *hi_res_remainder = exact - hi_res as f64;
 
-        self.uinput.write_raw(&buf[..offset])
+    if hi_res.signum() == -discrete_remainder.signum() {
+        *discrete_remainder = 0;
     }
+    *discrete_remainder += hi_res;
+
+    let discrete = *discrete_remainder / V120_PER_CLICK;
+    *discrete_remainder -= discrete * V120_PER_CLICK;
+
+    (hi_res, discrete)
 }
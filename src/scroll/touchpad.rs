@@ -0,0 +1,176 @@
+use std::f64::consts::TAU;
+use std::fs::File;
+use std::io::Write;
+use std::mem;
+
+use crate::errors::{Error, ErrorKind, Result, trace};
+
+use super::event::InputEvent;
+use super::utils::setup_uinput_touchpad;
+use super::constants::{
+    EV_SYN,
+    EV_KEY,
+    EV_ABS,
+    SYN_REPORT,
+    ABS_X,
+    ABS_Y,
+    ABS_MT_SLOT,
+    ABS_MT_TRACKING_ID,
+    ABS_MT_POSITION_X,
+    ABS_MT_POSITION_Y,
+    BTN_TOUCH,
+    BTN_TOOL_FINGER,
+    BTN_TOOL_DOUBLETAP,
+    BTN_TOOL_TRIPLETAP,
+    BTN_TOOL_QUADTAP,
+    BTN_TOOL_QUINTTAP,
+    TOUCHPAD_MAX_X,
+    TOUCHPAD_MAX_Y,
+    TOUCHPAD_RESOLUTION,
+    TOUCHPAD_SLOTS,
+    TOUCHPAD_FINGER_SPREAD,
+};
+
+const EVENT_SIZE: usize = mem::size_of::<InputEvent>();
+
+// Gesture deltas are normalized to a 1000dpi device, 0.0254mm per unit
+const UNITS_PER_DELTA: f64 = TOUCHPAD_RESOLUTION as f64 * 0.0254;
+
+pub struct VirtualTouchpad {
+    device: File,
+    fingers: usize,
+    center: (f64, f64),
+    scale: f64,
+    angle: f64,
+    tracking_id: i32,
+}
+
+impl VirtualTouchpad {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            device: setup_uinput_touchpad()?,
+            fingers: 0,
+            center: (0.0, 0.0),
+            scale: 1.0,
+            angle: 0.0,
+            tracking_id: 0,
+        })
+    }
+
+    pub fn is_touching(&self) -> bool {
+        self.fingers > 0
+    }
+
+    pub fn begin(&mut self, fingers: u32) -> Result<()> {
+        if self.is_touching() {
+            self.end()?;
+        }
+
+        self.fingers = (fingers as usize).clamp(1, TOUCHPAD_SLOTS);
+        self.center = (TOUCHPAD_MAX_X as f64 / 2.0, TOUCHPAD_MAX_Y as f64 / 2.0);
+        self.scale = 1.0;
+        self.angle = 0.0;
+        trace!(fingers = self.fingers, "Virtual touchpad gesture began");
+
+        let mut events = Vec::new();
+        for slot in 0..self.fingers {
+            self.tracking_id = (self.tracking_id + 1) % u16::MAX as i32;
+            events.push((EV_ABS as u16, ABS_MT_SLOT, slot as i32));
+            events.push((EV_ABS as u16, ABS_MT_TRACKING_ID, self.tracking_id));
+        }
+        self.push_positions(&mut events);
+        events.push((EV_KEY as u16, BTN_TOUCH, 1));
+        events.push((EV_KEY as u16, tool_button(self.fingers), 1));
+
+        self.write(events)
+    }
+
+    pub fn update(&mut self, dx: f64, dy: f64, scale: f64, angle_delta: f64) -> Result<()> {
+        if !self.is_touching() {
+            return Ok(());
+        }
+
+        self.center.0 += dx * UNITS_PER_DELTA;
+        self.center.1 += dy * UNITS_PER_DELTA;
+        self.scale = scale;
+        self.angle += angle_delta.to_radians();
+
+        let mut events = Vec::new();
+        self.push_positions(&mut events);
+
+        self.write(events)
+    }
+
+    pub fn end(&mut self) -> Result<()> {
+        if !self.is_touching() {
+            return Ok(());
+        }
+
+        let mut events = Vec::new();
+        for slot in 0..self.fingers {
+            events.push((EV_ABS as u16, ABS_MT_SLOT, slot as i32));
+            events.push((EV_ABS as u16, ABS_MT_TRACKING_ID, -1));
+        }
+        events.push((EV_KEY as u16, BTN_TOUCH, 0));
+        events.push((EV_KEY as u16, tool_button(self.fingers), 0));
+        self.fingers = 0;
+        trace!("Virtual touchpad gesture ended");
+
+        self.write(events)
+    }
+
+    fn push_positions(&self, events: &mut Vec<(u16, u16, i32)>) {
+        let radius = if self.fingers > 1 {
+            TOUCHPAD_FINGER_SPREAD * self.scale
+        } else {
+            0.0
+        };
+
+        for slot in 0..self.fingers {
+            let angle = self.angle + TAU * slot as f64 / self.fingers as f64;
+            let x = (self.center.0 + radius * angle.cos()).round() as i32;
+            let y = (self.center.1 + radius * angle.sin()).round() as i32;
+            let x = x.clamp(0, TOUCHPAD_MAX_X);
+            let y = y.clamp(0, TOUCHPAD_MAX_Y);
+
+            events.push((EV_ABS as u16, ABS_MT_SLOT, slot as i32));
+            events.push((EV_ABS as u16, ABS_MT_POSITION_X, x));
+            events.push((EV_ABS as u16, ABS_MT_POSITION_Y, y));
+            if slot == 0 {
+                events.push((EV_ABS as u16, ABS_X, x));
+                events.push((EV_ABS as u16, ABS_Y, y));
+            }
+        }
+    }
+
+    fn write(&mut self, mut events: Vec<(u16, u16, i32)>) -> Result<()> {
+        events.push((EV_SYN, SYN_REPORT, 0));
+
+        let mut buf = Vec::with_capacity(events.len() * EVENT_SIZE);
+        for (type_, code, value) in events {
+            let event = InputEvent {
+                type_,
+                code,
+                value,
+                ..Default::default()
+            };
+            let bytes: [u8; EVENT_SIZE] = unsafe { mem::transmute(event) };
+            buf.extend_from_slice(&bytes);
+        }
+
+        self.device.write_all(&buf).map_err(|e| {
+            Error::wrap(e, ErrorKind::Write)
+                .with_msg("scroll: Failed to write event to virtual touchpad")
+        })
+    }
+}
+
+fn tool_button(fingers: usize) -> u16 {
+    match fingers {
+        1 => BTN_TOOL_FINGER,
+        2 => BTN_TOOL_DOUBLETAP,
+        3 => BTN_TOOL_TRIPLETAP,
+        4 => BTN_TOOL_QUADTAP,
+        _ => BTN_TOOL_QUINTTAP,
+    }
+}
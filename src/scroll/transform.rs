@@ -0,0 +1,222 @@
+use crate::config::{AxisLock, InputDevice, ScrollCurve, ScrollTransform};
+use crate::synq::{ScrollEvent, ScrollSource as ProtoScrollSource};
+
+use super::constants::{ScrollSource, BEZIER_ITERATIONS};
+
+#[derive(Debug, Clone)]
+pub struct ScrollPipeline {
+    transforms: Vec<ScrollTransform>,
+    sign: f64,
+    modifier: f64,
+}
+
+impl ScrollPipeline {
+    pub fn new(transforms: Vec<ScrollTransform>) -> Self {
+        Self {
+            transforms,
+            sign: 1.0,
+            modifier: 1.0,
+        }
+    }
+
+    pub fn for_device(device: &InputDevice) -> Self {
+        Self {
+            transforms: device.scroll_transform.clone(),
+            sign: if device.scroll_reverse { -1.0 } else { 1.0 },
+            modifier: device.scroll_modifier,
+        }
+    }
+
+    pub fn apply(&self, source: ScrollSource, delta_x: f64, delta_y: f64) -> (f64, f64) {
+        let transform = self.transforms.iter()
+            .find(|t| t.source.is_none_or(|s| s == source));
+
+        let (delta_x, delta_y) = match transform {
+            Some(transform) => transform_deltas(transform, delta_x, delta_y),
+            None => (delta_x, delta_y),
+        };
+
+        let scale = self.sign * self.modifier;
+        (delta_x * scale, delta_y * scale)
+    }
+
+    pub fn apply_event(&self, event: &mut ScrollEvent) {
+        let (delta_x, delta_y) = self.apply(scroll_source(event), event.delta_x, event.delta_y);
+        event.delta_x = delta_x;
+        event.delta_y = delta_y;
+    }
+}
+
+pub fn scroll_source(event: &ScrollEvent) -> ScrollSource {
+    match event.source() {
+        ProtoScrollSource::Wheel => ScrollSource::Wheel,
+        ProtoScrollSource::Finger => ScrollSource::Finger,
+        ProtoScrollSource::Continuous => ScrollSource::Continuous,
+    }
+}
+
+fn transform_deltas(transform: &ScrollTransform, delta_x: f64, delta_y: f64) -> (f64, f64) {
+    let (mut x, mut y) = if transform.swap_axes {
+        (delta_y, delta_x)
+    } else {
+        (delta_x, delta_y)
+    };
+
+    match transform.axis_lock {
+        Some(AxisLock::Horizontal) => y = 0.0,
+        Some(AxisLock::Vertical) => x = 0.0,
+        Some(AxisLock::Dominant) if x.abs() >= y.abs() => y = 0.0,
+        Some(AxisLock::Dominant) => x = 0.0,
+        None => {}
+    }
+
+    let shape = |delta: f64| {
+        if delta.abs() < transform.deadzone {
+            return 0.0;
+        }
+        match &transform.curve {
+            Some(curve) => delta.signum() * apply_curve(curve, delta.abs()),
+            None => delta,
+        }
+    };
+
+    (shape(x) * transform.multiplier_x, shape(y) * transform.multiplier_y)
+}
+
+fn apply_curve(curve: &ScrollCurve, magnitude: f64) -> f64 {
+    match *curve {
+        ScrollCurve::Power { exponent, range } => {
+            range * (magnitude / range).powf(exponent)
+        }
+        ScrollCurve::Bezier { points, range } => {
+            let input = magnitude / range;
+            if input >= 1.0 {
+                return magnitude;
+            }
+            range * bezier(points, input)
+        }
+    }
+}
+
+// Cubic bezier from (0, 0) to (1, 1) with the two inner control points,
+// solved for t by bisection since x(t) is monotonic for x1, x2 in [0, 1]
+fn bezier([x1, y1, x2, y2]: [f64; 4], x: f64) -> f64 {
+    let cubic = |p1: f64, p2: f64, t: f64| {
+        let u = 1.0 - t;
+        3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t
+    };
+
+    let (mut lo, mut hi) = (0.0, 1.0);
+    for _ in 0..BEZIER_ITERATIONS {
+        let mid = (lo + hi) / 2.0;
+        if cubic(x1, x2, mid) < x {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    cubic(y1, y2, (lo + hi) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform() -> ScrollTransform {
+        ScrollTransform {
+            source: None,
+            multiplier_x: 1.0,
+            multiplier_y: 1.0,
+            curve: None,
+            swap_axes: false,
+            axis_lock: None,
+            deadzone: 0.0,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn bezier_linear_is_identity() {
+        for x in [0.0, 0.1, 0.25, 0.5, 0.9, 1.0] {
+            assert_close(bezier([0.25, 0.25, 0.75, 0.75], x), x);
+        }
+    }
+
+    #[test]
+    fn bezier_ease_in_stays_below_linear() {
+        let points = [0.42, 0.0, 1.0, 1.0];
+        assert_close(bezier(points, 0.0), 0.0);
+        assert_close(bezier(points, 1.0), 1.0);
+        for x in [0.1, 0.3, 0.5, 0.7, 0.9] {
+            assert!(bezier(points, x) < x);
+        }
+    }
+
+    #[test]
+    fn bezier_curve_is_linear_above_range() {
+        let curve = ScrollCurve::Bezier { points: [0.42, 0.0, 1.0, 1.0], range: 15.0 };
+        assert_close(apply_curve(&curve, 15.0), 15.0);
+        assert_close(apply_curve(&curve, 40.0), 40.0);
+        assert!(apply_curve(&curve, 5.0) < 5.0);
+    }
+
+    #[test]
+    fn power_curve_keeps_range_and_sign() {
+        let pipeline = ScrollPipeline::new(vec![ScrollTransform {
+            curve: Some(ScrollCurve::Power { exponent: 2.0, range: 10.0 }),
+            ..transform()
+        }]);
+
+        let (x, y) = pipeline.apply(ScrollSource::Finger, -10.0, 5.0);
+        assert_close(x, -10.0);
+        assert_close(y, 2.5);
+    }
+
+    #[test]
+    fn axis_lock() {
+        let lock = |axis_lock| ScrollPipeline::new(vec![ScrollTransform {
+            axis_lock: Some(axis_lock),
+            ..transform()
+        }]);
+
+        assert_eq!(lock(AxisLock::Horizontal).apply(ScrollSource::Finger, 3.0, 4.0), (3.0, 0.0));
+        assert_eq!(lock(AxisLock::Vertical).apply(ScrollSource::Finger, 3.0, 4.0), (0.0, 4.0));
+        assert_eq!(lock(AxisLock::Dominant).apply(ScrollSource::Finger, 3.0, 4.0), (0.0, 4.0));
+        assert_eq!(lock(AxisLock::Dominant).apply(ScrollSource::Finger, -5.0, 4.0), (-5.0, 0.0));
+    }
+
+    #[test]
+    fn swap_deadzone_and_multipliers() {
+        let pipeline = ScrollPipeline::new(vec![ScrollTransform {
+            swap_axes: true,
+            deadzone: 1.0,
+            multiplier_x: 2.0,
+            multiplier_y: 3.0,
+            ..transform()
+        }]);
+
+        assert_eq!(pipeline.apply(ScrollSource::Finger, 4.0, 0.5), (0.0, 12.0));
+    }
+
+    #[test]
+    fn first_matching_source_applies() {
+        let pipeline = ScrollPipeline::new(vec![
+            ScrollTransform {
+                source: Some(ScrollSource::Wheel),
+                multiplier_y: 2.0,
+                ..transform()
+            },
+            ScrollTransform {
+                multiplier_y: 0.5,
+                ..transform()
+            },
+        ]);
+
+        assert_eq!(pipeline.apply(ScrollSource::Wheel, 0.0, 4.0), (0.0, 8.0));
+        assert_eq!(pipeline.apply(ScrollSource::Finger, 0.0, 4.0), (0.0, 2.0));
+    }
+}
@@ -2,7 +2,8 @@ use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::mem;
 use std::os::unix::io::{AsRawFd, RawFd};
-use std::sync::{Arc, Mutex};
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock, Mutex};
 
 use crate::errors::{Error, ErrorKind, Result};
 use super::event::InputEvent;
@@ -16,6 +17,7 @@ use super::constants::{
     UI_SET_SNDBIT,
     UI_SET_FFBIT,
     UI_SET_SWBIT,
+    UI_SET_PROPBIT,
     UI_ABS_SETUP,
     UI_DEV_SETUP,
     UI_DEV_CREATE,
@@ -36,14 +38,47 @@ use super::constants::{
     LED_MAX,
     SND_MAX,
     FF_MAX,
+    REL_X,
+    REL_Y,
     REL_WHEEL,
     REL_HWHEEL,
     REL_WHEEL_HI_RES,
     REL_HWHEEL_HI_RES,
-    SCROLL_DEVICE_NAME,
-    SCROLL_DEVICE_ID,
+    KEY_ESC,
+    BTN_MISC,
+    BTN_LEFT,
+    BTN_TASK,
+    UINPUT_MAX_NAME_SIZE,
+    ABS_X,
+    ABS_Y,
+    ABS_MT_SLOT,
+    ABS_MT_TRACKING_ID,
+    ABS_MT_POSITION_X,
+    ABS_MT_POSITION_Y,
+    INPUT_PROP_POINTER,
+    INPUT_PROP_BUTTONPAD,
+    BTN_TOUCH,
+    BTN_TOOL_FINGER,
+    BTN_TOOL_QUINTTAP,
+    BTN_TOOL_DOUBLETAP,
+    BTN_TOOL_TRIPLETAP,
+    BTN_TOOL_QUADTAP,
+    TOUCHPAD_DEVICE_NAME,
+    TOUCHPAD_DEVICE_ID,
+    TOUCHPAD_MAX_X,
+    TOUCHPAD_MAX_Y,
+    TOUCHPAD_RESOLUTION,
+    TOUCHPAD_SLOTS,
 };
 
+// Extra capabilities on the clone that injects for the whole destination,
+// beyond what the source device reports
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UinputCapabilities {
+    pub pointer: bool,
+    pub keyboard: bool,
+}
+
 #[repr(C)]
 struct UinputAbsSetup {
     code: u16,
@@ -64,7 +99,7 @@ struct AbsInfo {
 #[repr(C)]
 struct UinputSetup {
     id: [u16; 4],
-    name: [u8; 80],
+    name: [u8; UINPUT_MAX_NAME_SIZE],
     ff_effects_max: u32,
 }
 
@@ -82,7 +117,53 @@ const fn eviocgabs(abs: u32) -> libc::c_ulong {
     (2u64 << 30) | (24u64 << 16) | (0x45u64 << 8) | (0x40 + abs) as u64
 }
 
-pub(crate) fn setup_uinput_merged(source_fd: RawFd) -> Result<File> {
+const fn ui_get_sysname(len: u32) -> libc::c_ulong {
+    (2u64 << 30) | (((len as u64) & 0x3fff) << 16) | (0x55u64 << 8) | 44
+}
+
+// Sysnames of the uinput devices this process created, so device scans
+// never grab and clone our own clones
+static OWN_DEVICES: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+fn register_own_device(ufd: RawFd) {
+    let mut buf = [0u8; 64];
+    if unsafe { libc::ioctl(ufd, ui_get_sysname(buf.len() as u32), buf.as_mut_ptr()) } < 0 {
+        return;
+    }
+
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let sysname = String::from_utf8_lossy(&buf[..len]).into_owned();
+    if let Ok(mut devices) = OWN_DEVICES.lock() {
+        devices.insert(sysname);
+    }
+}
+
+// Matches the event node of a device against the parent input device sysname
+pub(crate) fn is_own_device(devpath: &str) -> bool {
+    let Some(parent) = devpath.strip_prefix("/devices/virtual/input/")
+        .and_then(|rest| rest.split('/').next())
+    else {
+        return false;
+    };
+
+    OWN_DEVICES.lock().is_ok_and(|devices| devices.contains(parent))
+}
+
+// Units per millimeter of an absolute axis, when the device reports it
+pub(crate) fn abs_resolution(fd: RawFd, code: u16) -> Option<i32> {
+    let mut absinfo: AbsInfo = unsafe { mem::zeroed() };
+    if unsafe { libc::ioctl(fd, eviocgabs(code as u32), &mut absinfo) } < 0 {
+        return None;
+    }
+    (absinfo.resolution > 0).then_some(absinfo.resolution)
+}
+
+pub(crate) fn setup_uinput_merged(
+    source_fd: RawFd,
+    name: &str,
+    id: [u16; 4],
+    capabilities: UinputCapabilities,
+) -> Result<File> {
     let uinput = OpenOptions::new()
         .read(true)
         .write(true)
@@ -105,8 +186,20 @@ pub(crate) fn setup_uinput_merged(source_fd: RawFd) -> Result<File> {
                 .with_msg("scroll: Failed to get event types from source device"));
         }
 
-        if bit_is_set(&ev_bits, EV_KEY as usize) {
+        if capabilities.pointer || capabilities.keyboard || bit_is_set(&ev_bits, EV_KEY as usize) {
             libc::ioctl(ufd, UI_SET_EVBIT, EV_KEY);
+        }
+        if capabilities.pointer {
+            for code in BTN_LEFT..=BTN_TASK {
+                libc::ioctl(ufd, UI_SET_KEYBIT, code as libc::c_int);
+            }
+        }
+        if capabilities.keyboard {
+            for code in KEY_ESC..BTN_MISC {
+                libc::ioctl(ufd, UI_SET_KEYBIT, code as libc::c_int);
+            }
+        }
+        if bit_is_set(&ev_bits, EV_KEY as usize) {
             let mut key_bits = [0u8; (KEY_MAX + 7) / 8 + 1];
             if libc::ioctl(
                 source_fd,
@@ -136,6 +229,10 @@ pub(crate) fn setup_uinput_merged(source_fd: RawFd) -> Result<File> {
                 }
             }
         }
+        if capabilities.pointer {
+            libc::ioctl(ufd, UI_SET_RELBIT, REL_X as libc::c_int);
+            libc::ioctl(ufd, UI_SET_RELBIT, REL_Y as libc::c_int);
+        }
         libc::ioctl(ufd, UI_SET_RELBIT, REL_WHEEL as libc::c_int);
         libc::ioctl(ufd, UI_SET_RELBIT, REL_HWHEEL as libc::c_int);
         libc::ioctl(ufd, UI_SET_RELBIT, REL_WHEEL_HI_RES as libc::c_int);
@@ -248,12 +345,129 @@ pub(crate) fn setup_uinput_merged(source_fd: RawFd) -> Result<File> {
         }
 
         let mut setup: UinputSetup = mem::zeroed();
-        setup.id = SCROLL_DEVICE_ID;
-        setup.name[..SCROLL_DEVICE_NAME.len()].copy_from_slice(SCROLL_DEVICE_NAME);
+        let name = &name.as_bytes()[..name.len().min(UINPUT_MAX_NAME_SIZE - 1)];
+        setup.id = id;
+        setup.name[..name.len()].copy_from_slice(name);
+
+        libc::ioctl(ufd, UI_DEV_SETUP, &setup);
+        libc::ioctl(ufd, UI_DEV_CREATE, 0);
+    }
+    register_own_device(ufd);
+
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    Ok(uinput)
+}
+
+// A wheel-only mouse for injecting scroll when there is no device to clone
+pub(crate) fn setup_uinput_scroll(name: &str, id: [u16; 4]) -> Result<File> {
+    let uinput = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/uinput")
+        .map_err(|e| {
+            Error::wrap(e, ErrorKind::Read)
+                .with_msg("scroll: Failed to open uinput device")
+        })?;
+
+    let ufd = uinput.as_raw_fd();
+
+    unsafe {
+        libc::ioctl(ufd, UI_SET_EVBIT, EV_KEY);
+        for code in BTN_LEFT..=BTN_TASK {
+            libc::ioctl(ufd, UI_SET_KEYBIT, code as libc::c_int);
+        }
+
+        libc::ioctl(ufd, UI_SET_EVBIT, EV_REL);
+        for code in [REL_X, REL_Y, REL_WHEEL, REL_HWHEEL, REL_WHEEL_HI_RES, REL_HWHEEL_HI_RES] {
+            libc::ioctl(ufd, UI_SET_RELBIT, code as libc::c_int);
+        }
+
+        let mut setup: UinputSetup = mem::zeroed();
+        let name = &name.as_bytes()[..name.len().min(UINPUT_MAX_NAME_SIZE - 1)];
+        setup.id = id;
+        setup.name[..name.len()].copy_from_slice(name);
 
         libc::ioctl(ufd, UI_DEV_SETUP, &setup);
         libc::ioctl(ufd, UI_DEV_CREATE, 0);
     }
+    register_own_device(ufd);
+
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    Ok(uinput)
+}
+
+pub(crate) fn setup_uinput_touchpad() -> Result<File> {
+    let uinput = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/uinput")
+        .map_err(|e| {
+            Error::wrap(e, ErrorKind::Read)
+                .with_msg("scroll: Failed to open uinput device")
+        })?;
+
+    let ufd = uinput.as_raw_fd();
+
+    let axes = [
+        (ABS_X, TOUCHPAD_MAX_X),
+        (ABS_Y, TOUCHPAD_MAX_Y),
+        (ABS_MT_SLOT, TOUCHPAD_SLOTS as i32 - 1),
+        (ABS_MT_TRACKING_ID, u16::MAX as i32),
+        (ABS_MT_POSITION_X, TOUCHPAD_MAX_X),
+        (ABS_MT_POSITION_Y, TOUCHPAD_MAX_Y),
+    ];
+
+    unsafe {
+        libc::ioctl(ufd, UI_SET_PROPBIT, INPUT_PROP_POINTER);
+        libc::ioctl(ufd, UI_SET_PROPBIT, INPUT_PROP_BUTTONPAD);
+
+        libc::ioctl(ufd, UI_SET_EVBIT, EV_KEY);
+        for code in [
+            BTN_LEFT,
+            BTN_TOUCH,
+            BTN_TOOL_FINGER,
+            BTN_TOOL_DOUBLETAP,
+            BTN_TOOL_TRIPLETAP,
+            BTN_TOOL_QUADTAP,
+            BTN_TOOL_QUINTTAP,
+        ] {
+            libc::ioctl(ufd, UI_SET_KEYBIT, code as libc::c_int);
+        }
+
+        libc::ioctl(ufd, UI_SET_EVBIT, EV_ABS);
+        for (code, maximum) in axes {
+            libc::ioctl(ufd, UI_SET_ABSBIT, code as libc::c_int);
+
+            let position = code != ABS_MT_SLOT && code != ABS_MT_TRACKING_ID;
+            let abs_setup = UinputAbsSetup {
+                code,
+                _padding: 0,
+                absinfo: AbsInfo {
+                    value: 0,
+                    minimum: 0,
+                    maximum,
+                    fuzz: 0,
+                    flat: 0,
+                    resolution: if position { TOUCHPAD_RESOLUTION } else { 0 },
+                },
+            };
+            libc::ioctl(ufd, UI_ABS_SETUP, &abs_setup);
+        }
+
+        let mut setup: UinputSetup = mem::zeroed();
+        setup.id = TOUCHPAD_DEVICE_ID;
+        setup.name[..TOUCHPAD_DEVICE_NAME.len()].copy_from_slice(TOUCHPAD_DEVICE_NAME);
+
+        if libc::ioctl(ufd, UI_DEV_SETUP, &setup) != 0 {
+            return Err(Error::wrap(std::io::Error::last_os_error(), ErrorKind::Exec)
+                .with_msg("scroll: Failed to set up virtual touchpad"));
+        }
+        if libc::ioctl(ufd, UI_DEV_CREATE, 0) != 0 {
+            return Err(Error::wrap(std::io::Error::last_os_error(), ErrorKind::Exec)
+                .with_msg("scroll: Failed to create virtual touchpad"));
+        }
+    }
+    register_own_device(ufd);
 
     std::thread::sleep(std::time::Duration::from_millis(200));
     Ok(uinput)
@@ -261,28 +475,55 @@ pub(crate) fn setup_uinput_merged(source_fd: RawFd) -> Result<File> {
 
 #[derive(Clone)]
 pub struct SharedUinput {
-    inner: Arc<Mutex<File>>,
+    inner: Arc<Mutex<Option<File>>>,
 }
 
 impl SharedUinput {
-    pub fn new(source_fd: RawFd) -> Result<Self> {
-        let file = setup_uinput_merged(source_fd)?;
+    pub fn new(
+        source_fd: RawFd,
+        name: &str,
+        id: [u16; 4],
+        capabilities: UinputCapabilities,
+    ) -> Result<Self> {
+        let file = setup_uinput_merged(source_fd, name, id, capabilities)?;
         Ok(Self {
-            inner: Arc::new(Mutex::new(file)),
+            inner: Arc::new(Mutex::new(Some(file))),
         })
     }
 
-    #[allow(unused)]
-    pub fn write_event(&self, event: &InputEvent) -> Result<()> {
-        let bytes: [u8; mem::size_of::<InputEvent>()] = unsafe { mem::transmute(*event) };
+    pub fn standalone(name: &str, id: [u16; 4]) -> Result<Self> {
+        let file = setup_uinput_scroll(name, id)?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Some(file))),
+        })
+    }
+
+    pub fn detached() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn rebuild(
+        &self,
+        source_fd: RawFd,
+        name: &str,
+        id: [u16; 4],
+        capabilities: UinputCapabilities,
+    ) -> Result<()> {
+        let file = setup_uinput_merged(source_fd, name, id, capabilities)?;
         let mut guard = self.inner.lock().map_err(|_| {
             Error::new(ErrorKind::Exec)
                 .with_msg("scroll: Failed to acquire uinput lock")
         })?;
-        (&mut *guard).write_all(&bytes).map_err(|e| {
-            Error::wrap(e, ErrorKind::Write)
-                .with_msg("scroll: Failed to write event to uinput")
-        })
+        *guard = Some(file);
+        Ok(())
+    }
+
+    #[allow(unused)]
+    pub fn write_event(&self, event: &InputEvent) -> Result<()> {
+        let bytes: [u8; mem::size_of::<InputEvent>()] = unsafe { mem::transmute(*event) };
+        self.write_raw(&bytes)
     }
 
     pub fn write_raw(&self, bytes: &[u8]) -> Result<()> {
@@ -290,7 +531,11 @@ impl SharedUinput {
             Error::new(ErrorKind::Exec)
                 .with_msg("scroll: Failed to acquire uinput lock")
         })?;
-        (&mut *guard).write_all(bytes).map_err(|e| {
+        let file = guard.as_mut().ok_or_else(|| {
+            Error::new(ErrorKind::Exec)
+                .with_msg("scroll: Virtual device is not available")
+        })?;
+        file.write_all(bytes).map_err(|e| {
             Error::wrap(e, ErrorKind::Write)
                 .with_msg("scroll: Failed to write event to uinput")
         })
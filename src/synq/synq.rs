@@ -8,6 +8,53 @@ pub struct ScrollEvent {
     pub delta_x: f64,
     #[prost(double, tag = "3")]
     pub delta_y: f64,
+    #[prost(uint64, tag = "4")]
+    pub time_usec: u64,
+    #[prost(bool, tag = "5")]
+    pub stop: bool,
+    #[prost(bool, tag = "6")]
+    pub mirror: bool,
+}
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct PointerEvent {
+    #[prost(sint32, tag = "1")]
+    pub dx: i32,
+    #[prost(sint32, tag = "2")]
+    pub dy: i32,
+    #[prost(uint32, tag = "3")]
+    pub button: u32,
+    #[prost(bool, tag = "4")]
+    pub pressed: bool,
+    #[prost(uint64, tag = "5")]
+    pub time_usec: u64,
+}
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct KeyEvent {
+    #[prost(uint32, tag = "1")]
+    pub code: u32,
+    #[prost(uint32, tag = "2")]
+    pub value: u32,
+    #[prost(uint64, tag = "3")]
+    pub time_usec: u64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct GestureEvent {
+    #[prost(enumeration = "GestureKind", tag = "1")]
+    pub kind: i32,
+    #[prost(enumeration = "GesturePhase", tag = "2")]
+    pub phase: i32,
+    #[prost(uint32, tag = "3")]
+    pub fingers: u32,
+    #[prost(double, tag = "4")]
+    pub dx: f64,
+    #[prost(double, tag = "5")]
+    pub dy: f64,
+    #[prost(double, tag = "6")]
+    pub scale: f64,
+    #[prost(double, tag = "7")]
+    pub angle_delta: f64,
+    #[prost(uint64, tag = "8")]
+    pub time_usec: u64,
 }
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct ClipboardEvent {
@@ -22,6 +69,17 @@ pub struct ActiveEvent {
     pub peer: ::prost::alloc::string::String,
     #[prost(uint64, tag = "2")]
     pub clock: u64,
+    #[prost(uint64, tag = "3")]
+    pub epoch: u64,
+    #[prost(string, tag = "4")]
+    pub source: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "5")]
+    pub ack_id: u64,
+}
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ActiveSyncRequest {
+    #[prost(string, tag = "1")]
+    pub peer: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct ActivateEvent {
@@ -30,6 +88,86 @@ pub struct ActivateEvent {
     #[prost(bool, tag = "2")]
     pub state: bool,
 }
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct PingEvent {
+    #[prost(string, tag = "1")]
+    pub peer: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub sequence: u64,
+    #[prost(int64, tag = "3")]
+    pub send_time: i64,
+    #[prost(int64, tag = "4")]
+    pub receive_time: i64,
+    #[prost(int64, tag = "5")]
+    pub reply_time: i64,
+}
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct HelloEvent {
+    #[prost(string, tag = "1")]
+    pub peer: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub protocol_version: u32,
+    #[prost(string, tag = "3")]
+    pub version: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "4")]
+    pub features: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(uint64, tag = "5")]
+    pub max_message_size: u64,
+}
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SessionOpen {
+    #[prost(string, tag = "1")]
+    pub peer: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub hello: ::core::option::Option<HelloEvent>,
+}
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SessionChallenge {
+    #[prost(string, tag = "1")]
+    pub nonce: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SessionAuth {
+    #[prost(string, tag = "1")]
+    pub proof: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SessionMessage {
+    #[prost(oneof = "session_message::Payload", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13")]
+    pub payload: ::core::option::Option<session_message::Payload>,
+}
+/// Nested message and enum types in `SessionMessage`.
+pub mod session_message {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Payload {
+        #[prost(message, tag = "1")]
+        Open(super::SessionOpen),
+        #[prost(message, tag = "2")]
+        Scroll(super::ScrollEvent),
+        #[prost(message, tag = "3")]
+        Clipboard(super::ClipboardEvent),
+        #[prost(message, tag = "4")]
+        ActiveState(super::ActiveEvent),
+        #[prost(message, tag = "5")]
+        Ping(super::PingEvent),
+        #[prost(message, tag = "6")]
+        Pong(super::PingEvent),
+        #[prost(message, tag = "7")]
+        Hello(super::HelloEvent),
+        #[prost(message, tag = "8")]
+        Pointer(super::PointerEvent),
+        #[prost(message, tag = "9")]
+        Key(super::KeyEvent),
+        #[prost(message, tag = "10")]
+        Gesture(super::GestureEvent),
+        #[prost(message, tag = "11")]
+        ActiveAck(super::ActiveEvent),
+        #[prost(message, tag = "12")]
+        Challenge(super::SessionChallenge),
+        #[prost(message, tag = "13")]
+        Auth(super::SessionAuth),
+    }
+}
 #[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct Empty {
 }
@@ -62,5 +200,66 @@ impl ScrollSource {
         }
     }
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum GestureKind {
+    Swipe = 0,
+    Pinch = 1,
+    Hold = 2,
+}
+impl GestureKind {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Swipe => "GESTURE_KIND_SWIPE",
+            Self::Pinch => "GESTURE_KIND_PINCH",
+            Self::Hold => "GESTURE_KIND_HOLD",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GESTURE_KIND_SWIPE" => Some(Self::Swipe),
+            "GESTURE_KIND_PINCH" => Some(Self::Pinch),
+            "GESTURE_KIND_HOLD" => Some(Self::Hold),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum GesturePhase {
+    Begin = 0,
+    Update = 1,
+    End = 2,
+    Cancel = 3,
+}
+impl GesturePhase {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Begin => "GESTURE_PHASE_BEGIN",
+            Self::Update => "GESTURE_PHASE_UPDATE",
+            Self::End => "GESTURE_PHASE_END",
+            Self::Cancel => "GESTURE_PHASE_CANCEL",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GESTURE_PHASE_BEGIN" => Some(Self::Begin),
+            "GESTURE_PHASE_UPDATE" => Some(Self::Update),
+            "GESTURE_PHASE_END" => Some(Self::End),
+            "GESTURE_PHASE_CANCEL" => Some(Self::Cancel),
+            _ => None,
+        }
+    }
+}
 include!("synq.tonic.rs");
 // @@protoc_insertion_point(module)
@@ -171,6 +171,81 @@ pub mod synq_service_client {
                 .insert(GrpcMethod::new("synq.SynqService", "ActiveState"));
             self.inner.unary(req, path, codec).await
         }
+        pub async fn ping(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PingEvent>,
+        ) -> std::result::Result<tonic::Response<super::PingEvent>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/synq.SynqService/Ping");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("synq.SynqService", "Ping"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn session(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::SessionMessage>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::SessionMessage>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/synq.SynqService/Session");
+            let mut req = request.into_streaming_request();
+            req.extensions_mut().insert(GrpcMethod::new("synq.SynqService", "Session"));
+            self.inner.streaming(req, path, codec).await
+        }
+        pub async fn hello(
+            &mut self,
+            request: impl tonic::IntoRequest<super::HelloEvent>,
+        ) -> std::result::Result<tonic::Response<super::HelloEvent>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/synq.SynqService/Hello");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("synq.SynqService", "Hello"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn sync_active_state(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ActiveSyncRequest>,
+        ) -> std::result::Result<tonic::Response<super::ActiveEvent>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/synq.SynqService/SyncActiveState");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("synq.SynqService", "SyncActiveState"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -202,6 +277,28 @@ pub mod synq_service_server {
             &self,
             request: tonic::Request<super::ActiveEvent>,
         ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status>;
+        async fn ping(
+            &self,
+            request: tonic::Request<super::PingEvent>,
+        ) -> std::result::Result<tonic::Response<super::PingEvent>, tonic::Status>;
+        /// Server streaming response type for the Session method.
+        type SessionStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::SessionMessage, tonic::Status>,
+            >
+            + std::marker::Send
+            + 'static;
+        async fn session(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::SessionMessage>>,
+        ) -> std::result::Result<tonic::Response<Self::SessionStream>, tonic::Status>;
+        async fn hello(
+            &self,
+            request: tonic::Request<super::HelloEvent>,
+        ) -> std::result::Result<tonic::Response<super::HelloEvent>, tonic::Status>;
+        async fn sync_active_state(
+            &self,
+            request: tonic::Request<super::ActiveSyncRequest>,
+        ) -> std::result::Result<tonic::Response<super::ActiveEvent>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct SynqServiceServer<T> {
@@ -457,6 +554,183 @@ pub mod synq_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/synq.SynqService/Ping" => {
+                    #[allow(non_camel_case_types)]
+                    struct PingSvc<T: SynqService>(pub Arc<T>);
+                    impl<T: SynqService> tonic::server::UnaryService<super::PingEvent>
+                    for PingSvc<T> {
+                        type Response = super::PingEvent;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PingEvent>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SynqService>::ping(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = PingSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/synq.SynqService/Session" => {
+                    #[allow(non_camel_case_types)]
+                    struct SessionSvc<T: SynqService>(pub Arc<T>);
+                    impl<
+                        T: SynqService,
+                    > tonic::server::StreamingService<super::SessionMessage>
+                    for SessionSvc<T> {
+                        type Response = super::SessionMessage;
+                        type ResponseStream = T::SessionStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                tonic::Streaming<super::SessionMessage>,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SynqService>::session(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SessionSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/synq.SynqService/Hello" => {
+                    #[allow(non_camel_case_types)]
+                    struct HelloSvc<T: SynqService>(pub Arc<T>);
+                    impl<T: SynqService> tonic::server::UnaryService<super::HelloEvent>
+                    for HelloSvc<T> {
+                        type Response = super::HelloEvent;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::HelloEvent>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SynqService>::hello(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = HelloSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/synq.SynqService/SyncActiveState" => {
+                    #[allow(non_camel_case_types)]
+                    struct SyncActiveStateSvc<T: SynqService>(pub Arc<T>);
+                    impl<T: SynqService> tonic::server::UnaryService<super::ActiveSyncRequest>
+                    for SyncActiveStateSvc<T> {
+                        type Response = super::ActiveEvent;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ActiveSyncRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SynqService>::sync_active_state(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SyncActiveStateSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         let mut response = http::Response::new(
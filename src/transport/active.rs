@@ -1,24 +1,54 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
+use std::path::Path;
+use std::time::Instant;
 
+use tokio::fs;
 use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Duration};
 use tokio_util::sync::CancellationToken;
-use tonic::transport::Channel;
 
-use crate::errors::{error, trace};
+use crate::errors::{error, info, trace};
 use crate::errors::{Error, ErrorKind};
-use crate::config::PeerConfig;
+use crate::config::{Config, PeerConfig};
+use crate::metrics::METRICS;
+use crate::utils;
 use crate::synq::{
-    synq_service_client::SynqServiceClient,
-    ActiveEvent, ActivateEvent,
+    session_message::Payload,
+    ActiveEvent, ActivateEvent, ActiveSyncRequest,
 };
 
+use super::client::connect;
+use super::session::SessionRegistry;
+use super::heartbeat::PeerHealthMap;
+use super::hello::CapabilityMap;
+use super::constants::{
+    ACTIVE_LEASE_DURATION,
+    ACTIVE_STATE_RETRIES,
+    ACTIVE_STATE_RETRY_DELAY,
+    ACTIVE_STATE_TIMEOUT,
+    ACTIVE_SYNC_INTERVAL,
+    EPOCH_EXTENSION,
+    FEATURE_ACTIVE_ACK,
+    HEARTBEAT_INTERVAL,
+};
+
+// The source currently allowed to drive a destination. It is kept after it
+// expires or is released so activate requests go back to the last owner
+struct Lease {
+    source: String,
+    expires: u64,
+}
 
 #[derive(Clone)]
 pub struct ActiveState {
     active_peer: Arc<RwLock<Option<String>>>,
     clock: Arc<AtomicU64>,
+    epoch: Arc<AtomicU64>,
+    source: Arc<RwLock<String>>,
+    lease: Arc<Mutex<Option<Lease>>>,
     host_active: Arc<AtomicBool>,
     host_public_key: Arc<String>,
     last_scroll: Arc<AtomicU64>,
@@ -30,6 +60,9 @@ impl ActiveState {
         Self {
             active_peer: Arc::new(RwLock::new(None)),
             clock: Arc::new(AtomicU64::new(0)),
+            epoch: Arc::new(AtomicU64::new(0)),
+            source: Arc::new(RwLock::new(String::new())),
+            lease: Arc::new(Mutex::new(None)),
             host_active: Arc::new(AtomicBool::new(false)),
             host_public_key: Arc::new(host_public_key),
             last_scroll: Arc::new(AtomicU64::new(0)),
@@ -65,11 +98,119 @@ impl ActiveState {
         self.clock.load(Ordering::SeqCst)
     }
 
-    pub fn set_active(&self, peer: String, clock: u64) {
+    pub fn get_epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    // The source stamps its state with an epoch that grows on every start so
+    // destinations can tell a restarted source from a stale update
+    pub fn begin_epoch(&self, epoch: u64) {
+        self.epoch.store(epoch, Ordering::SeqCst);
+    }
+
+    pub fn host_public_key(&self) -> &str {
+        &self.host_public_key
+    }
+
+    pub fn current_event(&self) -> ActiveEvent {
+        ActiveEvent {
+            peer: self.get_active_peer()
+                .unwrap_or_else(|| self.host_public_key.to_string()),
+            clock: self.get_clock(),
+            epoch: self.get_epoch(),
+            source: self.host_public_key.to_string(),
+            ack_id: 0,
+        }
+    }
+
+    pub fn lease_holder(&self) -> Option<String> {
+        let now = utils::mono_time_ms();
+        self.lease.lock().unwrap().as_ref()
+            .filter(|l| now < l.expires)
+            .map(|l| l.source.clone())
+    }
+
+    pub fn lease_source(&self) -> Option<String> {
+        self.lease.lock().unwrap().as_ref().map(|l| l.source.clone())
+    }
+
+    pub fn state_source(&self) -> String {
+        self.source.read().unwrap().clone()
+    }
+
+    // Applies the state of a source on a destination. Each source counts its
+    // clock on its own, and only the source holding the lease, or any source
+    // while the lease is free, may change the state.
+    pub fn apply(&self, source: &str, event: &ActiveEvent) -> bool {
+        let now = utils::mono_time_ms();
+        let mut lease = self.lease.lock().unwrap();
+
+        if lease.as_ref().is_some_and(|l| l.source != source && now < l.expires) {
+            return false;
+        }
+
+        let same_source = *self.source.read().unwrap() == source;
+
+        // Another source only matters to an inactive destination once it
+        // selects it
+        if !same_source && event.peer != *self.host_public_key && !self.is_host_active() {
+            return false;
+        }
+
+        let current_epoch = self.get_epoch();
+        let current_clock = self.get_clock();
+        if same_source && event.epoch != 0 && event.epoch < current_epoch {
+            return false;
+        }
+
+        // Older peers reset without an epoch when they start
+        let restarted = !same_source
+            || event.epoch > current_epoch
+            || (event.clock == 0 && event.epoch == 0);
+        if !restarted && event.clock < current_clock {
+            return false;
+        }
+
+        let changed = restarted || event.clock > current_clock;
+        if changed {
+            if event.clock == 0 {
+                self.reset();
+                self.epoch.store(event.epoch, Ordering::SeqCst);
+            } else {
+                self.set_active(event.peer.clone(), event.clock, event.epoch);
+            }
+            *self.source.write().unwrap() = source.to_string();
+        }
+
+        if event.peer == *self.host_public_key {
+            *lease = Some(Lease {
+                source: source.to_string(),
+                expires: now + ACTIVE_LEASE_DURATION,
+            });
+        } else if let Some(lease) = lease.as_mut().filter(|l| l.source == source) {
+            lease.expires = 0;
+        }
+
+        changed
+    }
+
+    fn set_active(&self, peer: String, clock: u64, epoch: u64) {
         let is_host = peer == *self.host_public_key;
         *self.active_peer.write().unwrap() = Some(peer);
-        self.clock.store(clock, Ordering::SeqCst);
+        self.epoch.store(epoch, Ordering::SeqCst);
+        let old_clock = self.clock.swap(clock, Ordering::SeqCst);
         self.host_active.store(is_host, Ordering::Release);
+
+        if old_clock != clock {
+            METRICS.active_changes.inc("remote");
+            METRICS.active_clock.set(clock);
+        }
+    }
+
+    // Newer changes are delivered on their own, a reset carries clock 0 and
+    // has to reach new destinations whatever the clock is by then
+    pub fn is_superseded(&self, event: &ActiveEvent) -> bool {
+        event.clock != 0 && self.get_clock() != event.clock
     }
 
     pub fn increment_and_set(&self, peer: String) -> u64 {
@@ -77,6 +218,10 @@ impl ActiveState {
         let new_clock = self.clock.fetch_add(1, Ordering::SeqCst) + 1;
         *self.active_peer.write().unwrap() = Some(peer);
         self.host_active.store(is_host, Ordering::Release);
+
+        METRICS.active_changes.inc("local");
+        METRICS.active_clock.set(new_clock);
+
         new_clock
     }
 
@@ -93,9 +238,41 @@ impl ActiveState {
         *self.active_peer.write().unwrap() = None;
         self.clock.store(0, Ordering::SeqCst);
         self.host_active.store(false, Ordering::Release);
+
+        METRICS.active_changes.inc("reset");
+        METRICS.active_clock.set(0);
     }
 }
 
+// Epochs have to grow across restarts even when the wall clock steps back,
+// so the last one is kept next to the config. The first one is seeded from
+// the clock to stay above the start times earlier versions sent.
+pub(crate) async fn next_epoch(config_path: &Path) -> crate::errors::Result<u64> {
+    let path = config_path.with_extension(EPOCH_EXTENSION);
+
+    let last = match fs::read_to_string(&path).await {
+        Ok(contents) => contents.trim().parse::<u64>()
+            .map_err(|e| Error::wrap(e, ErrorKind::Parse)
+                .with_msg("transport: Invalid epoch file")
+                .with_ctx("path", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => utils::unix_time_us() as u64,
+        Err(e) => {
+            return Err(Error::wrap(e, ErrorKind::Read)
+                .with_msg("transport: Failed to read epoch file")
+                .with_ctx("path", path.display()));
+        }
+    };
+
+    let epoch = last + 1;
+    fs::write(&path, epoch.to_string())
+        .await
+        .map_err(|e| Error::wrap(e, ErrorKind::Write)
+            .with_msg("transport: Failed to write epoch file")
+            .with_ctx("path", path.display()))?;
+
+    Ok(epoch)
+}
+
 pub enum ActiveRequestEvent {
     Activate,
     Deactivate,
@@ -105,27 +282,37 @@ pub struct ActiveTransport;
 
 impl ActiveTransport {
     pub fn start(
-        peers: &[PeerConfig],
+        config: Arc<RwLock<Config>>,
         host_public_key: String,
         active_state: ActiveState,
+        health: PeerHealthMap,
         cancel: CancellationToken,
     ) -> mpsc::Sender<ActiveRequestEvent> {
-        let destination_peers: Vec<_> = peers.iter()
-            .filter(|p| p.scroll_destination)
-            .cloned()
-            .collect();
-
-        let source_peer = peers.iter()
-            .find(|p| p.scroll_source)
-            .cloned();
-
         let (tx, rx) = mpsc::channel::<ActiveRequestEvent>(32);
 
+        if config.read().unwrap().server.scroll_destination {
+            tokio::spawn({
+                let config = config.clone();
+                let host_public_key = host_public_key.clone();
+                let active_state = active_state.clone();
+                let cancel = cancel.clone();
+
+                async move {
+                    run_active_resync(
+                        config,
+                        host_public_key,
+                        active_state,
+                        health,
+                        cancel,
+                    ).await;
+                }
+            });
+        }
+
         tokio::spawn(async move {
             run_active_handler(
                 rx,
-                source_peer,
-                destination_peers,
+                config,
                 host_public_key,
                 active_state,
                 cancel,
@@ -138,8 +325,7 @@ impl ActiveTransport {
 
 async fn run_active_handler(
     mut rx: mpsc::Receiver<ActiveRequestEvent>,
-    source_peer: Option<PeerConfig>,
-    _destination_peers: Vec<PeerConfig>,
+    config: Arc<RwLock<Config>>,
     host_public_key: String,
     active_state: ActiveState,
     cancel: CancellationToken,
@@ -155,6 +341,7 @@ async fn run_active_handler(
             }
         };
 
+        let source_peer = activate_source(&config.read().unwrap(), &active_state);
         let Some(ref source) = source_peer else {
             trace!("No scroll source peer configured, cannot send active request");
             continue;
@@ -179,9 +366,14 @@ async fn run_active_handler(
                     "Sending activate request to source",
                 );
 
-                match send_activate_request(&source.address, &host_public_key, true).await {
+                let start = Instant::now();
+                let result = send_activate_request(
+                    &source.address, &host_public_key, true).await;
+                METRICS.activate_latency.observe(start.elapsed().as_secs_f64());
+
+                match result {
                     Ok(response) => {
-                        active_state.set_active(response.peer.clone(), response.clock);
+                        active_state.apply(&source.public_key, &response);
                         trace!(
                             peer = %response.peer,
                             clock = response.clock,
@@ -211,9 +403,14 @@ async fn run_active_handler(
                     "Sending deactivate request to source",
                 );
 
-                match send_activate_request(&source.address, &host_public_key, false).await {
+                let start = Instant::now();
+                let result = send_activate_request(
+                    &source.address, &host_public_key, false).await;
+                METRICS.activate_latency.observe(start.elapsed().as_secs_f64());
+
+                match result {
                     Ok(response) => {
-                        active_state.set_active(response.peer.clone(), response.clock);
+                        active_state.apply(&source.public_key, &response);
                         trace!(
                             peer = %response.peer,
                             clock = response.clock,
@@ -250,44 +447,244 @@ async fn send_activate_request(
     Ok(response.into_inner())
 }
 
-pub async fn send_active_state(
+// Activate requests go to the source holding the lease, or the one that held
+// it last, since only that source may drive this destination
+fn activate_source(config: &Config, active_state: &ActiveState) -> Option<PeerConfig> {
+    let lease_source = active_state.lease_source();
+    let mut sources = config.peers.iter().filter(|p| p.scroll_source);
+
+    sources.clone()
+        .find(|p| lease_source.as_ref() == Some(&p.public_key))
+        .or_else(|| sources.next())
+        .cloned()
+}
+
+// A destination asks each source for its state whenever the source comes up,
+// which covers missed updates while either side was restarting
+async fn run_active_resync(
+    config: Arc<RwLock<Config>>,
+    host_public_key: String,
+    active_state: ActiveState,
+    health: PeerHealthMap,
+    cancel: CancellationToken,
+) {
+    let mut sources_up: HashSet<String> = HashSet::new();
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = sleep(Duration::from_millis(HEARTBEAT_INTERVAL)) => {}
+        }
+
+        let sources: Vec<PeerConfig> = config.read().unwrap().peers.iter()
+            .filter(|p| p.scroll_source)
+            .cloned()
+            .collect();
+
+        for source in sources {
+            let up = health.read().unwrap().get(&source.public_key)
+                .is_some_and(|h| h.up);
+            if !up {
+                sources_up.remove(&source.public_key);
+                continue;
+            }
+            if sources_up.contains(&source.public_key) {
+                continue;
+            }
+
+            match sync_active_state(&source.address, &host_public_key).await {
+                Ok(event) => {
+                    sources_up.insert(source.public_key.clone());
+                    if active_state.apply(&source.public_key, &event) {
+                        info!("Active state synced from {}", source.address);
+                    }
+                    trace!(
+                        peer = %event.peer,
+                        clock = event.clock,
+                        epoch = event.epoch,
+                        "Received active state sync",
+                    );
+                }
+                Err(e) => error(&e),
+            }
+        }
+    }
+}
+
+async fn sync_active_state(
     address: &str,
-    peer: &str,
-    clock: u64,
-) -> crate::errors::Result<()> {
+    host_public_key: &str,
+) -> crate::errors::Result<ActiveEvent> {
     let mut client = connect(address).await?;
 
-    let event = ActiveEvent {
-        peer: peer.to_string(),
-        clock,
+    let request = ActiveSyncRequest {
+        peer: host_public_key.to_string(),
     };
 
-    client.active_state(event)
+    let response = client.sync_active_state(request)
         .await
         .map_err(|e| Error::wrap(e, ErrorKind::Network)
-            .with_msg("transport: Active state send failed")
+            .with_msg("transport: Active state sync failed")
             .with_ctx("address", address))?;
 
-    Ok(())
+    Ok(response.into_inner())
 }
 
-async fn connect(address: &str) -> crate::errors::Result<SynqServiceClient<Channel>> {
-    let host_port = match address.find('@') {
-        Some(i) => &address[i + 1..],
-        None => address,
-    };
+#[derive(Clone)]
+pub struct ActiveDelivery {
+    sessions: SessionRegistry,
+    capabilities: CapabilityMap,
+    active_state: ActiveState,
+}
 
-    let url = format!("http://{}", host_port);
+impl ActiveDelivery {
+    pub fn new(
+        sessions: SessionRegistry,
+        capabilities: CapabilityMap,
+        active_state: ActiveState,
+    ) -> Self {
+        Self {
+            sessions,
+            capabilities,
+            active_state,
+        }
+    }
 
-    let channel = Channel::from_shared(url)
-        .map_err(|e| Error::wrap(e, ErrorKind::Network)
-            .with_msg("transport: Invalid peer address")
-            .with_ctx("address", address))?
-        .connect()
-        .await
-        .map_err(|e| Error::wrap(e, ErrorKind::Network)
-            .with_msg("transport: Failed to connect to peer")
-            .with_ctx("address", address))?;
+    pub async fn deliver(&self, dest: &PeerConfig, event: ActiveEvent) {
+        let mut delay = ACTIVE_STATE_RETRY_DELAY;
+
+        for attempt in 1..=ACTIVE_STATE_RETRIES {
+            if self.active_state.is_superseded(&event) {
+                return;
+            }
+
+            match self.send(dest, &event).await {
+                Ok(()) => return,
+                Err(e) if attempt == ACTIVE_STATE_RETRIES => {
+                    error(&e);
+                    return;
+                }
+                Err(e) => {
+                    METRICS.active_retries.inc(&dest.address);
+                    trace!(
+                        address = &dest.address,
+                        attempt = attempt,
+                        error = %e,
+                        "Active state delivery failed, retrying",
+                    );
+                }
+            }
 
-    Ok(SynqServiceClient::new(channel))
+            sleep(Duration::from_millis(delay)).await;
+            delay *= 2;
+        }
+    }
+
+    pub async fn send(&self, dest: &PeerConfig, event: &ActiveEvent) -> crate::errors::Result<()> {
+        let acked = self.capabilities.read().unwrap().get(&dest.public_key)
+            .is_some_and(|c| c.supports(FEATURE_ACTIVE_ACK));
+
+        let result = timeout(Duration::from_millis(ACTIVE_STATE_TIMEOUT), async {
+            if acked {
+                if let Some(result) = self.sessions
+                    .send_active_state(&dest.public_key, event.clone()).await
+                {
+                    return result;
+                }
+            } else if self.sessions
+                .send(&dest.public_key, Payload::ActiveState(event.clone())).await == Some(true)
+            {
+                return Ok(());
+            }
+
+            let mut client = connect(&dest.address).await?;
+
+            client.active_state(event.clone())
+                .await
+                .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                    .with_msg("transport: Active state send failed")
+                    .with_ctx("address", &dest.address))?;
+
+            Ok(())
+        }).await;
+
+        result.unwrap_or_else(|e| Err(Error::wrap(e, ErrorKind::Timeout)
+            .with_msg("transport: Active state send timed out")
+            .with_ctx("address", &dest.address)))
+    }
+
+    pub fn active_state(&self) -> &ActiveState {
+        &self.active_state
+    }
+}
+
+// The source rebroadcasts its state so a destination that missed an update
+// converges without waiting for the next change
+pub(crate) async fn run_active_sync(
+    delivery: ActiveDelivery,
+    config: Arc<RwLock<Config>>,
+    cancel: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = sleep(Duration::from_millis(ACTIVE_SYNC_INTERVAL)) => {}
+        }
+
+        let event = delivery.active_state().current_event();
+        let peers = config.read().unwrap().peers.clone();
+
+        for dest in peers.into_iter().filter(|p| p.scroll_destination) {
+            tokio::spawn({
+                let delivery = delivery.clone();
+                let event = event.clone();
+
+                async move {
+                    if let Err(e) = delivery.send(&dest, &event).await {
+                        trace!(
+                            address = &dest.address,
+                            error = %e,
+                            "Active state sync failed",
+                        );
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(peer: &str, clock: u64) -> ActiveEvent {
+        ActiveEvent {
+            peer: peer.to_string(),
+            clock,
+            epoch: 1,
+            source: "host".to_string(),
+            ack_id: 0,
+        }
+    }
+
+    #[test]
+    fn reset_is_not_superseded_after_activation() {
+        let state = ActiveState::new("host".to_string());
+        state.increment_and_set("guest".to_string());
+        state.increment_and_set("host".to_string());
+
+        assert!(state.get_clock() > 0);
+        assert!(!state.is_superseded(&event("host", 0)));
+    }
+
+    #[test]
+    fn older_change_is_superseded() {
+        let state = ActiveState::new("host".to_string());
+        let clock = state.increment_and_set("guest".to_string());
+        let change = event("guest", clock);
+        assert!(!state.is_superseded(&change));
+
+        state.increment_and_set("host".to_string());
+        assert!(state.is_superseded(&change));
+    }
 }
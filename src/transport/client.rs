@@ -0,0 +1,28 @@
+use tonic::transport::Channel;
+
+use crate::errors::{Result, Error, ErrorKind};
+use crate::synq::synq_service_client::SynqServiceClient;
+
+// Peer addresses may carry a user@ prefix for ssh, only host:port is dialed
+pub(crate) fn host_port(address: &str) -> &str {
+    match address.find('@') {
+        Some(i) => &address[i + 1..],
+        None => address,
+    }
+}
+
+pub(crate) async fn connect(address: &str) -> Result<SynqServiceClient<Channel>> {
+    let url = format!("http://{}", host_port(address));
+
+    let channel = Channel::from_shared(url)
+        .map_err(|e| Error::wrap(e, ErrorKind::Network)
+            .with_msg("transport: Invalid peer address")
+            .with_ctx("address", address))?
+        .connect()
+        .await
+        .map_err(|e| Error::wrap(e, ErrorKind::Network)
+            .with_msg("transport: Failed to connect to peer")
+            .with_ctx("address", address))?;
+
+    Ok(SynqServiceClient::new(channel))
+}
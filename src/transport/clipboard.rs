@@ -1,17 +1,22 @@
 use std::sync::Arc;
 
 use tokio::sync::mpsc;
-use tonic::transport::Channel;
 
 use crate::errors::{error, trace};
 use crate::errors::{Result, Error, ErrorKind};
 use crate::crypto;
 use crate::crypto::KeyStore;
+use crate::metrics::METRICS;
 use crate::synq::{
-    synq_service_client::SynqServiceClient,
+    session_message::Payload,
     ClipboardEvent,
 };
 
+use super::client::connect;
+use super::session::SessionRegistry;
+use super::hello::CapabilityMap;
+use super::constants::MAX_MESSAGE_SIZE;
+
 pub struct ClipboardSendEvent {
     pub peer_address: String,
     pub peer_public_key: String,
@@ -24,12 +29,16 @@ impl ClipboardTransport {
     pub fn start(
         key_store: Arc<KeyStore>,
         public_key: String,
+        sessions: SessionRegistry,
+        capabilities: CapabilityMap,
     ) -> mpsc::Sender<ClipboardSendEvent> {
         let (tx, mut rx) = mpsc::channel::<ClipboardSendEvent>(16);
 
         tokio::spawn(async move {
             while let Some(event) = rx.recv().await {
                 if let Err(e) = send_clipboard(
+                    &sessions,
+                    &capabilities,
                     &key_store,
                     &public_key,
                     &event.peer_address,
@@ -46,6 +55,8 @@ impl ClipboardTransport {
 }
 
 async fn send_clipboard(
+    sessions: &SessionRegistry,
+    capabilities: &CapabilityMap,
     key_store: &KeyStore,
     our_public_key: &str,
     peer_address: &str,
@@ -56,43 +67,38 @@ async fn send_clipboard(
         key_store,
         peer_public_key,
         clipboard_text,
-    )?;
+    ).inspect_err(|_| METRICS.encryption_failures.inc("encrypt"))?;
+    let encrypted_len = encrypted.len() as u64;
+
+    let max_message_size = capabilities.read().unwrap().get(peer_public_key)
+        .map(|c| c.max_message_size)
+        .unwrap_or(MAX_MESSAGE_SIZE);
+    if encrypted_len > max_message_size {
+        return Err(Error::new(ErrorKind::Invalid)
+            .with_msg("transport: Clipboard exceeds peer message size limit")
+            .with_ctx("address", peer_address)
+            .with_ctx("size", encrypted_len)
+            .with_ctx("limit", max_message_size));
+    }
 
     let event = ClipboardEvent {
         client: our_public_key.to_string(),
         data: encrypted.into_bytes(),
     };
 
-    let mut client = connect(peer_address).await?;
+    let sent = sessions.send(peer_public_key, Payload::Clipboard(event.clone())).await;
+    if sent != Some(true) {
+        let mut client = connect(peer_address).await?;
 
-    client.clipboard(event)
-        .await
-        .map_err(|e| Error::wrap(e, ErrorKind::Network)
-            .with_msg("transport: Failed to send clipboard")
-            .with_ctx("address", peer_address))?;
+        client.clipboard(event)
+            .await
+            .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                .with_msg("transport: Failed to send clipboard")
+                .with_ctx("address", peer_address))?;
+    }
 
+    METRICS.clipboard_sent_bytes.inc_by(peer_address, encrypted_len);
     trace!("Clipboard sent to {}", peer_address);
 
     Ok(())
 }
-
-async fn connect(address: &str) -> Result<SynqServiceClient<Channel>> {
-    let host_port = match address.find('@') {
-        Some(i) => &address[i + 1..],
-        None => address,
-    };
-
-    let url = format!("http://{}", host_port);
-
-    let channel = Channel::from_shared(url)
-        .map_err(|e| Error::wrap(e, ErrorKind::Network)
-            .with_msg("transport: Invalid peer address")
-            .with_ctx("address", address))?
-        .connect()
-        .await
-        .map_err(|e| Error::wrap(e, ErrorKind::Network)
-            .with_msg("transport: Failed to connect to peer")
-            .with_ctx("address", address))?;
-
-    Ok(SynqServiceClient::new(channel))
-}
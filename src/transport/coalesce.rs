@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+use tokio::time::{sleep_until, Duration, Instant};
+
+use crate::synq::ScrollEvent;
+
+#[derive(Clone)]
+pub struct ScrollBuffer {
+    pending: Arc<Mutex<VecDeque<ScrollEvent>>>,
+    notify: Arc<Notify>,
+    flush_interval: Option<Duration>,
+}
+
+impl ScrollBuffer {
+    pub fn new(flush_interval: Option<u64>) -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(VecDeque::new())),
+            notify: Arc::new(Notify::new()),
+            flush_interval: flush_interval
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+        }
+    }
+
+    pub fn push(&self, event: ScrollEvent) -> bool {
+        let merged = {
+            let mut pending = self.pending.lock().unwrap();
+
+            // A pending stop ends its gesture, the next one starts a new entry
+            match pending.iter_mut().find(|e| e.source == event.source && !e.stop) {
+                Some(existing) => {
+                    existing.delta_x += event.delta_x;
+                    existing.delta_y += event.delta_y;
+                    existing.time_usec = event.time_usec;
+                    existing.stop = event.stop;
+                    true
+                }
+                None => {
+                    pending.push_back(event);
+                    false
+                }
+            }
+        };
+
+        self.notify.notify_one();
+        merged
+    }
+
+    // Deltas are stale once a connection is lost, but a pending stop still
+    // has to reach the destination to end its kinetic scroll
+    pub fn discard_deltas(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|e| e.stop);
+        for event in pending.iter_mut() {
+            event.delta_x = 0.0;
+            event.delta_y = 0.0;
+        }
+    }
+
+    pub async fn pop(&self, last_flush: &mut Option<Instant>) -> ScrollEvent {
+        if let (Some(interval), Some(last)) = (self.flush_interval, *last_flush) {
+            sleep_until(last + interval).await;
+        }
+
+        loop {
+            let event = self.pending.lock().unwrap().pop_front();
+            if let Some(event) = event {
+                *last_flush = Some(Instant::now());
+                return event;
+            }
+            self.notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synq::ScrollSource;
+
+    fn event(delta_y: f64, stop: bool) -> ScrollEvent {
+        ScrollEvent {
+            source: ScrollSource::Finger.into(),
+            delta_x: 0.0,
+            delta_y,
+            time_usec: 0,
+            stop,
+            mirror: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn stop_is_not_merged_into_next_gesture() {
+        let buffer = ScrollBuffer::new(None);
+        let mut last_flush = None;
+
+        assert!(!buffer.push(event(1.0, false)));
+        assert!(buffer.push(event(2.0, true)));
+        assert!(!buffer.push(event(4.0, false)));
+        assert!(buffer.push(event(8.0, false)));
+
+        let first = buffer.pop(&mut last_flush).await;
+        assert!(first.stop);
+        assert_eq!(first.delta_y, 3.0);
+
+        let second = buffer.pop(&mut last_flush).await;
+        assert!(!second.stop);
+        assert_eq!(second.delta_y, 12.0);
+    }
+}
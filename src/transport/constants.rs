@@ -0,0 +1,34 @@
+pub(crate) const HEARTBEAT_INTERVAL: u64 = 2000;
+pub(crate) const HEARTBEAT_TIMEOUT: u64 = 1000;
+pub(crate) const HEARTBEAT_FAILURES: u32 = 3;
+pub(crate) const SESSION_CAPACITY: usize = 64;
+pub(crate) const SESSION_RECONNECT_DELAY: u64 = 1000;
+pub(crate) const SESSION_AUTH_TIMEOUT: u64 = 5000;
+pub(crate) const ACTIVE_STATE_TIMEOUT: u64 = 1000;
+pub(crate) const ACTIVE_STATE_RETRIES: u32 = 5;
+pub(crate) const ACTIVE_STATE_RETRY_DELAY: u64 = 200;
+pub(crate) const ACTIVE_SYNC_INTERVAL: u64 = 5000;
+pub(crate) const ACTIVE_LEASE_DURATION: u64 = 3 * ACTIVE_SYNC_INTERVAL;
+pub(crate) const EPOCH_EXTENSION: &str = "epoch";
+pub(crate) const SOURCE_METADATA_KEY: &str = "synq-source";
+
+pub(crate) const PROTOCOL_VERSION: u32 = 1;
+pub(crate) const MIN_PROTOCOL_VERSION: u32 = 1;
+pub(crate) const MAX_MESSAGE_SIZE: u64 = 4 * 1024 * 1024;
+
+pub(crate) const FEATURE_SESSION: &str = "session";
+pub(crate) const FEATURE_PING: &str = "ping";
+pub(crate) const FEATURE_SCROLL_TIMESTAMP: &str = "scroll_timestamp";
+pub(crate) const FEATURE_POINTER: &str = "pointer";
+pub(crate) const FEATURE_KEYBOARD: &str = "keyboard";
+pub(crate) const FEATURE_GESTURE: &str = "gesture";
+pub(crate) const FEATURE_ACTIVE_ACK: &str = "active_ack";
+pub(crate) const FEATURES: &[&str] = &[
+    FEATURE_SESSION,
+    FEATURE_PING,
+    FEATURE_SCROLL_TIMESTAMP,
+    FEATURE_POINTER,
+    FEATURE_KEYBOARD,
+    FEATURE_GESTURE,
+    FEATURE_ACTIVE_ACK,
+];
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use tokio::time::{sleep, timeout, Duration};
+use tokio_util::sync::CancellationToken;
+use tonic::transport::Channel;
+
+use crate::errors::{info, warn, trace};
+use crate::errors::{Result, Error, ErrorKind};
+use crate::config::PeerConfig;
+use crate::synq::{
+    synq_service_client::SynqServiceClient,
+    PingEvent,
+};
+use crate::utils;
+
+use super::client::connect;
+use super::session::SessionRegistry;
+use super::hello::{CapabilityMap, hello};
+use super::constants::{
+    HEARTBEAT_INTERVAL,
+    HEARTBEAT_TIMEOUT,
+    HEARTBEAT_FAILURES,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct PeerHealth {
+    pub up: bool,
+    pub latency_ms: f64,
+    pub jitter_ms: f64,
+    pub clock_offset_ms: f64,
+    pub last_seen: Option<u64>,
+    pub failures: u32,
+}
+
+impl PeerHealth {
+    pub fn is_down(&self) -> bool {
+        !self.up && self.failures >= HEARTBEAT_FAILURES
+    }
+}
+
+pub type PeerHealthMap = Arc<RwLock<HashMap<String, PeerHealth>>>;
+
+#[derive(Clone)]
+pub struct HeartbeatTransport {
+    peers: Arc<Mutex<Vec<HeartbeatPeer>>>,
+    health: PeerHealthMap,
+    host_public_key: String,
+    sessions: SessionRegistry,
+    capabilities: CapabilityMap,
+    cancel: CancellationToken,
+}
+
+struct HeartbeatPeer {
+    address: String,
+    public_key: String,
+    cancel: CancellationToken,
+}
+
+impl HeartbeatTransport {
+    pub fn start(
+        peers: &[PeerConfig],
+        host_public_key: String,
+        health: PeerHealthMap,
+        sessions: SessionRegistry,
+        capabilities: CapabilityMap,
+        cancel: CancellationToken,
+    ) -> Self {
+        let transport = Self {
+            peers: Arc::new(Mutex::new(Vec::new())),
+            health,
+            host_public_key,
+            sessions,
+            capabilities,
+            cancel,
+        };
+        transport.update_peers(peers);
+        transport
+    }
+
+    pub fn update_peers(&self, peers: &[PeerConfig]) {
+        let mut heartbeat_peers = self.peers.lock().unwrap();
+
+        heartbeat_peers.retain(|hb| {
+            let keep = peers.iter().any(|p| is_heartbeat_peer(p)
+                && p.address == hb.address
+                && p.public_key == hb.public_key);
+            if !keep {
+                hb.cancel.cancel();
+                self.health.write().unwrap().remove(&hb.public_key);
+                self.capabilities.write().unwrap().remove(&hb.public_key);
+            }
+            keep
+        });
+
+        for peer in peers.iter().filter(|p| is_heartbeat_peer(p)) {
+            let exists = heartbeat_peers.iter().any(|hb| {
+                hb.address == peer.address && hb.public_key == peer.public_key
+            });
+            if exists {
+                continue;
+            }
+
+            let cancel = self.cancel.child_token();
+
+            tokio::spawn({
+                let address = peer.address.clone();
+                let public_key = peer.public_key.clone();
+                let host_public_key = self.host_public_key.clone();
+                let health = self.health.clone();
+                let sessions = self.sessions.clone();
+                let capabilities = self.capabilities.clone();
+                let cancel = cancel.clone();
+
+                async move {
+                    run_heartbeat(
+                        address,
+                        public_key,
+                        host_public_key,
+                        health,
+                        sessions,
+                        capabilities,
+                        cancel,
+                    ).await;
+                }
+            });
+
+            heartbeat_peers.push(HeartbeatPeer {
+                address: peer.address.clone(),
+                public_key: peer.public_key.clone(),
+                cancel,
+            });
+        }
+    }
+}
+
+fn is_heartbeat_peer(peer: &PeerConfig) -> bool {
+    peer.clipboard_destination || peer.scroll_destination || peer.scroll_source
+}
+
+struct PingSample {
+    rtt_ms: f64,
+    offset_ms: f64,
+}
+
+async fn run_heartbeat(
+    address: String,
+    public_key: String,
+    host_public_key: String,
+    health: PeerHealthMap,
+    sessions: SessionRegistry,
+    capabilities: CapabilityMap,
+    cancel: CancellationToken,
+) {
+    let mut client: Option<SynqServiceClient<Channel>> = None;
+    let mut sequence: u64 = 0;
+
+    loop {
+        sequence += 1;
+
+        let result = tokio::select! {
+            _ = cancel.cancelled() => return,
+            result = timeout(
+                Duration::from_millis(HEARTBEAT_TIMEOUT),
+                ping(
+                    &mut client,
+                    &sessions,
+                    &capabilities,
+                    &address,
+                    &public_key,
+                    &host_public_key,
+                    sequence,
+                ),
+            ) => {
+                match result {
+                    Ok(result) => result,
+                    Err(e) => Err(Error::wrap(e, ErrorKind::Timeout)
+                        .with_msg("transport: Ping timed out")
+                        .with_ctx("address", &address)),
+                }
+            }
+        };
+
+        match result {
+            Ok(sample) => record_success(&health, &address, &public_key, sample),
+            Err(e) => {
+                client = None;
+                record_failure(&health, &address, &public_key, &e);
+            }
+        }
+
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = sleep(Duration::from_millis(HEARTBEAT_INTERVAL)) => {}
+        }
+    }
+}
+
+async fn ping(
+    client: &mut Option<SynqServiceClient<Channel>>,
+    sessions: &SessionRegistry,
+    capabilities: &CapabilityMap,
+    address: &str,
+    public_key: &str,
+    host_public_key: &str,
+    sequence: u64,
+) -> Result<PingSample> {
+    let send_time = utils::unix_time_us();
+    let event = PingEvent {
+        peer: host_public_key.to_string(),
+        sequence,
+        send_time,
+        receive_time: 0,
+        reply_time: 0,
+    };
+
+    let response = match sessions.ping(public_key, event.clone()).await {
+        Some(result) => result?,
+        None => {
+            if client.is_none() {
+                let mut new_client = connect(address).await?;
+                hello(
+                    &mut new_client,
+                    capabilities,
+                    address,
+                    public_key,
+                    host_public_key,
+                ).await?;
+                *client = Some(new_client);
+            }
+            let Some(client) = client.as_mut() else {
+                return Err(Error::new(ErrorKind::Connection)
+                    .with_msg("transport: Ping client unavailable"));
+            };
+
+            client.ping(event)
+                .await
+                .map_err(|e| Error::wrap(e, ErrorKind::Network)
+                    .with_msg("transport: Ping failed")
+                    .with_ctx("address", address))?
+                .into_inner()
+        }
+    };
+    let receive_time = utils::unix_time_us();
+
+    if response.sequence != sequence {
+        return Err(Error::new(ErrorKind::Invalid)
+            .with_msg("transport: Ping sequence mismatch")
+            .with_ctx("address", address)
+            .with_ctx("sequence", sequence)
+            .with_ctx("response_sequence", response.sequence));
+    }
+
+    let server_time = response.reply_time - response.receive_time;
+    let rtt_us = (receive_time - send_time - server_time).max(0);
+    let offset_us = ((response.receive_time - send_time)
+        + (response.reply_time - receive_time)) / 2;
+
+    Ok(PingSample {
+        rtt_ms: rtt_us as f64 / 1000.0,
+        offset_ms: offset_us as f64 / 1000.0,
+    })
+}
+
+fn record_success(
+    health: &PeerHealthMap,
+    address: &str,
+    public_key: &str,
+    sample: PingSample,
+) {
+    let mut health = health.write().unwrap();
+    let peer = health.entry(public_key.to_string()).or_default();
+
+    if peer.last_seen.is_none() {
+        peer.latency_ms = sample.rtt_ms;
+        peer.clock_offset_ms = sample.offset_ms;
+    } else {
+        let delta = (sample.rtt_ms - peer.latency_ms).abs();
+        peer.jitter_ms += (delta - peer.jitter_ms) / 16.0;
+        peer.latency_ms += (sample.rtt_ms - peer.latency_ms) / 8.0;
+        peer.clock_offset_ms += (sample.offset_ms - peer.clock_offset_ms) / 8.0;
+    }
+    peer.last_seen = Some(utils::mono_time_ms());
+    peer.failures = 0;
+
+    if !peer.up {
+        peer.up = true;
+        info!(
+            "Peer {} is up: latency={:.2}ms offset={:.2}ms",
+            address, peer.latency_ms, peer.clock_offset_ms,
+        );
+    }
+
+    trace!(
+        address = address,
+        rtt_ms = sample.rtt_ms,
+        latency_ms = peer.latency_ms,
+        jitter_ms = peer.jitter_ms,
+        offset_ms = peer.clock_offset_ms,
+        "Heartbeat",
+    );
+}
+
+fn record_failure(
+    health: &PeerHealthMap,
+    address: &str,
+    public_key: &str,
+    e: &Error,
+) {
+    let mut health = health.write().unwrap();
+    let peer = health.entry(public_key.to_string()).or_default();
+
+    peer.failures = peer.failures.saturating_add(1);
+
+    trace!(
+        address = address,
+        failures = peer.failures,
+        error = %e,
+        "Heartbeat failed",
+    );
+
+    if peer.failures == HEARTBEAT_FAILURES {
+        peer.up = false;
+        warn!("Peer {} is down: {}", address, e);
+    }
+}
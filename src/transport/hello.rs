@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use tonic::Code;
+use tonic::transport::Channel;
+
+use crate::constants::VERSION;
+use crate::errors::{info, warn};
+use crate::errors::{Result, Error, ErrorKind};
+use crate::synq::{
+    synq_service_client::SynqServiceClient,
+    HelloEvent,
+};
+
+use super::constants::{
+    PROTOCOL_VERSION,
+    MIN_PROTOCOL_VERSION,
+    FEATURES,
+    MAX_MESSAGE_SIZE,
+};
+
+#[derive(Debug, Clone)]
+pub struct PeerCapabilities {
+    pub protocol_version: u32,
+    pub version: String,
+    pub features: Vec<String>,
+    pub max_message_size: u64,
+}
+
+impl PeerCapabilities {
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+pub type CapabilityMap = Arc<RwLock<HashMap<String, PeerCapabilities>>>;
+
+pub(crate) fn local_hello(public_key: &str) -> HelloEvent {
+    HelloEvent {
+        peer: public_key.to_string(),
+        protocol_version: PROTOCOL_VERSION,
+        version: VERSION.to_string(),
+        features: FEATURES.iter().map(|f| f.to_string()).collect(),
+        max_message_size: MAX_MESSAGE_SIZE,
+    }
+}
+
+pub(crate) fn negotiate(remote: &HelloEvent) -> Result<PeerCapabilities> {
+    if remote.protocol_version < MIN_PROTOCOL_VERSION {
+        return Err(Error::new(ErrorKind::Invalid)
+            .with_msg("transport: Incompatible peer protocol version")
+            .with_ctx("local_version", VERSION)
+            .with_ctx("local_protocol", PROTOCOL_VERSION)
+            .with_ctx("peer_version", &remote.version)
+            .with_ctx("peer_protocol", remote.protocol_version));
+    }
+
+    let features = FEATURES.iter()
+        .filter(|f| remote.features.iter().any(|r| r == *f))
+        .map(|f| f.to_string())
+        .collect();
+
+    let max_message_size = match remote.max_message_size {
+        0 => MAX_MESSAGE_SIZE,
+        size => size.min(MAX_MESSAGE_SIZE),
+    };
+
+    Ok(PeerCapabilities {
+        protocol_version: remote.protocol_version.min(PROTOCOL_VERSION),
+        version: remote.version.clone(),
+        features,
+        max_message_size,
+    })
+}
+
+pub(crate) fn record(
+    capabilities: &CapabilityMap,
+    address: &str,
+    public_key: &str,
+    caps: PeerCapabilities,
+) {
+    let mut capabilities = capabilities.write().unwrap();
+    let changed = capabilities.get(public_key)
+        .is_none_or(|c| c.version != caps.version || c.features != caps.features);
+    if changed {
+        info!(
+            "Negotiated with peer {}: version={} protocol={} features=[{}]",
+            address, caps.version, caps.protocol_version, caps.features.join(","),
+        );
+    }
+    capabilities.insert(public_key.to_string(), caps);
+}
+
+pub(crate) async fn hello(
+    client: &mut SynqServiceClient<Channel>,
+    capabilities: &CapabilityMap,
+    address: &str,
+    public_key: &str,
+    host_public_key: &str,
+) -> Result<()> {
+    let response = match client.hello(local_hello(host_public_key)).await {
+        Ok(response) => response.into_inner(),
+        Err(status) if status.code() == Code::Unimplemented => {
+            warn!("Peer {} does not support version negotiation, upgrade synq on it",
+                address);
+            return Err(Error::new(ErrorKind::Invalid)
+                .with_msg("transport: Peer does not support version negotiation")
+                .with_ctx("address", address)
+                .with_ctx("local_version", VERSION));
+        }
+        Err(status) => {
+            return Err(Error::wrap(status, ErrorKind::Network)
+                .with_msg("transport: Hello failed")
+                .with_ctx("address", address));
+        }
+    };
+
+    let caps = negotiate(&response)
+        .map_err(|e| e.with_ctx("address", address))?;
+    record(capabilities, address, public_key, caps);
+
+    Ok(())
+}
@@ -4,7 +4,12 @@ mod server;
 mod scroll;
 mod clipboard;
 mod active;
+mod heartbeat;
+mod session;
+mod coalesce;
+mod client;
+mod hello;
 mod constants;
 
-pub use transport::{Transport, PeerState, ScrollInjectRx};
-pub use active::{ActiveState, send_active_state};
+pub use transport::{Transport, PeerState, ScrollInjectRx, PointerInjectRx, KeyInjectRx, GestureInjectRx};
+pub use active::ActiveState;
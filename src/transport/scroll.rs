@@ -1,21 +1,28 @@
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
 use tokio::sync::mpsc;
 use tokio::time::{sleep, Duration};
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::CancellationToken;
-use tonic::transport::Channel;
+use tonic::Request;
+use tonic::metadata::MetadataValue;
 
-use crate::errors::{error, info, warn};
-use crate::errors::{Result, Error, ErrorKind};
+use crate::errors::{error, info};
+use crate::errors::{Error, ErrorKind};
 use crate::config::PeerConfig;
-use crate::synq::{
-    synq_service_client::SynqServiceClient,
-    ScrollEvent,
-};
+use crate::metrics::METRICS;
+use crate::scroll::ScrollPipeline;
+use crate::synq::session_message::Payload;
+
+use super::client::connect;
 use super::PeerState;
 use super::active::ActiveState;
+use super::heartbeat::PeerHealthMap;
+use super::session::SessionRegistry;
+use super::coalesce::ScrollBuffer;
+use super::hello::CapabilityMap;
+use super::constants::{FEATURE_SCROLL_TIMESTAMP, SOURCE_METADATA_KEY};
 
 const CHANNEL_CAPACITY: usize = 32;
 const RECONNECT_DELAY_MS: u64 = 1000;
@@ -24,89 +31,185 @@ const STATE_DISCONNECTED: u8 = 0;
 const STATE_CONNECTING: u8 = 1;
 const STATE_CONNECTED: u8 = 2;
 
-pub struct ScrollTransport;
+#[derive(Clone)]
+pub struct ScrollTransport {
+    peers: Arc<Mutex<Vec<PeerInfo>>>,
+    sessions: SessionRegistry,
+    host_public_key: String,
+    mirror: Arc<AtomicBool>,
+    flush_interval: Option<u64>,
+    cancel: CancellationToken,
+}
 
 struct PeerInfo {
+    address: String,
     public_key: String,
-    tx: mpsc::Sender<ScrollEvent>,
+    mirror: bool,
+    pipeline: ScrollPipeline,
+    buffer: ScrollBuffer,
+    state: Arc<AtomicU8>,
+    cancel: CancellationToken,
 }
 
 impl ScrollTransport {
     pub fn start(
         peers: &[PeerConfig],
         active_state: ActiveState,
+        health: PeerHealthMap,
+        sessions: SessionRegistry,
+        capabilities: CapabilityMap,
+        flush_interval: Option<u64>,
         cancel: CancellationToken,
-    ) -> (mpsc::Sender<ScrollEvent>, Vec<(String, Arc<AtomicU8>)>) {
-        let scroll_peers: Vec<_> = peers.iter()
-            .filter(|p| p.scroll_destination)
-            .collect();
-
-        if scroll_peers.is_empty() {
-            let (tx, _rx) = mpsc::channel(1);
-            return (tx, Vec::new());
-        }
-
-        let (main_tx, mut main_rx) = mpsc::channel::<ScrollEvent>(CHANNEL_CAPACITY);
-
-        let mut peer_infos: Vec<PeerInfo> = Vec::with_capacity(scroll_peers.len());
-        let mut peer_states = Vec::with_capacity(scroll_peers.len());
-
-        for peer in scroll_peers {
-            let (peer_tx, peer_rx) = mpsc::channel(CHANNEL_CAPACITY);
-            let state = Arc::new(AtomicU8::new(STATE_DISCONNECTED));
-
-            peer_states.push((peer.address.clone(), state.clone()));
-
-            tokio::spawn({
-                let address = peer.address.clone();
-                let cancel = cancel.clone();
-                let state = state.clone();
-
-                async move {
-                    run_peer_connection(address, peer_rx, state, cancel).await;
-                }
-            });
-
-            peer_infos.push(PeerInfo {
-                public_key: peer.public_key.clone(),
-                tx: peer_tx,
-            });
-        }
+    ) -> (Self, ScrollBuffer) {
+        let main_buffer = ScrollBuffer::new(None);
+
+        let transport = Self {
+            peers: Arc::new(Mutex::new(Vec::new())),
+            sessions,
+            host_public_key: active_state.host_public_key().to_string(),
+            mirror: Arc::new(AtomicBool::new(false)),
+            flush_interval,
+            cancel: cancel.clone(),
+        };
+        transport.update_peers(peers);
 
         tokio::spawn({
+            let peers = transport.peers.clone();
+            let mirror = transport.mirror.clone();
+            let main_buffer = main_buffer.clone();
             let cancel = cancel.clone();
 
             async move {
+                let mut last_flush = None;
+
                 loop {
                     let event = tokio::select! {
                         _ = cancel.cancelled() => break,
-                        result = main_rx.recv() => {
-                            match result {
-                                Some(e) => e,
-                                None => break,
-                            }
-                        }
+                        event = main_buffer.pop(&mut last_flush) => event,
                     };
 
                     let Some(active_peer) = active_state.get_active_peer() else {
                         continue;
                     };
 
-                    for peer_info in &peer_infos {
-                        if peer_info.public_key == active_peer {
-                            if let Err(e) = peer_info.tx.try_send(event) {
-                                warn!("scroll: Dropped scroll event: {}", e);
-                            }
+                    let peers = peers.lock().unwrap();
+
+                    // Mirroring only kicks in while one of the mirror peers is
+                    // active so scrolling the host or another peer stays local
+                    let mirroring = mirror.load(Ordering::Relaxed) && peers.iter()
+                        .any(|p| p.mirror && p.public_key == active_peer);
+
+                    for peer_info in peers.iter() {
+                        let selected = if mirroring {
+                            peer_info.mirror
+                        } else {
+                            peer_info.public_key == active_peer
+                        };
+                        if !selected {
+                            continue;
+                        }
+
+                        let peer_down = health.read().unwrap().get(&peer_info.public_key)
+                            .is_some_and(|h| h.is_down());
+                        if peer_down {
+                            continue;
+                        }
+
+                        // Copies to the other mirror peers are injected there
+                        // even though the pointer is not on them
+                        let mut event = event;
+                        event.mirror = mirroring && peer_info.public_key != active_peer;
+                        let timestamps = capabilities.read().unwrap().get(&peer_info.public_key)
+                            .is_some_and(|c| c.supports(FEATURE_SCROLL_TIMESTAMP));
+                        if !timestamps {
+                            event.time_usec = 0;
+                        }
+
+                        peer_info.pipeline.apply_event(&mut event);
+                        if event.delta_x == 0.0 && event.delta_y == 0.0 && !event.stop {
+                            continue;
+                        }
+
+                        if peer_info.buffer.push(event) {
+                            METRICS.scroll_coalesced.inc(&peer_info.address);
                         }
                     }
                 }
             }
         });
 
-        (main_tx, peer_states)
+        (transport, main_buffer)
+    }
+
+    pub fn update_peers(&self, peers: &[PeerConfig]) {
+        let mut peer_infos = self.peers.lock().unwrap();
+
+        peer_infos.retain(|info| {
+            let keep = peers.iter().any(|p| p.scroll_destination
+                && p.address == info.address
+                && p.public_key == info.public_key);
+            if !keep {
+                info!("Stopping scroll connection to {}", info.address);
+                info.cancel.cancel();
+            }
+            keep
+        });
+
+        for peer in peers.iter().filter(|p| p.scroll_destination) {
+            let existing = peer_infos.iter_mut().find(|info| {
+                info.address == peer.address && info.public_key == peer.public_key
+            });
+            if let Some(info) = existing {
+                info.mirror = peer.mirror;
+                info.pipeline = ScrollPipeline::new(peer.scroll_transform.clone());
+                continue;
+            }
+
+            let buffer = ScrollBuffer::new(self.flush_interval);
+            let state = Arc::new(AtomicU8::new(STATE_DISCONNECTED));
+            let cancel = self.cancel.child_token();
+
+            tokio::spawn({
+                let address = peer.address.clone();
+                let public_key = peer.public_key.clone();
+                let buffer = buffer.clone();
+                let sessions = self.sessions.clone();
+                let host_public_key = self.host_public_key.clone();
+                let cancel = cancel.clone();
+                let state = state.clone();
+
+                async move {
+                    run_peer_connection(
+                        address, public_key, host_public_key, buffer, state, sessions, cancel,
+                    ).await;
+                }
+            });
+
+            peer_infos.push(PeerInfo {
+                address: peer.address.clone(),
+                public_key: peer.public_key.clone(),
+                mirror: peer.mirror,
+                pipeline: ScrollPipeline::new(peer.scroll_transform.clone()),
+                buffer,
+                state,
+                cancel,
+            });
+        }
+    }
+
+    pub fn set_mirror(&self, enabled: bool) {
+        let previous = self.mirror.swap(enabled, Ordering::Relaxed);
+        if previous != enabled {
+            info!("Scroll mirror {}", if enabled { "enabled" } else { "disabled" });
+        }
+    }
+
+    pub fn peer_states(&self) -> Vec<(String, Arc<AtomicU8>)> {
+        self.peers.lock().unwrap().iter()
+            .map(|info| (info.address.clone(), info.state.clone()))
+            .collect()
     }
 
-    #[allow(dead_code)]
     pub fn atomic_to_peer_state(state: &AtomicU8) -> PeerState {
         match state.load(Ordering::Relaxed) {
             STATE_CONNECTED => PeerState::Connected,
@@ -118,11 +221,37 @@ impl ScrollTransport {
 
 async fn run_peer_connection(
     address: String,
-    mut rx: mpsc::Receiver<ScrollEvent>,
+    public_key: String,
+    host_public_key: String,
+    buffer: ScrollBuffer,
     state: Arc<AtomicU8>,
+    sessions: SessionRegistry,
     cancel: CancellationToken,
 ) {
+    let mut last_flush = None;
+    // Retries during one outage count as a single reconnect
+    let mut disconnected = false;
+
     loop {
+        if sessions.has_session(&public_key) {
+            state.store(STATE_CONNECTED, Ordering::Relaxed);
+            disconnected = false;
+
+            let event = tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = sleep(Duration::from_millis(RECONNECT_DELAY_MS)) => continue,
+                event = buffer.pop(&mut last_flush) => event,
+            };
+
+            match sessions.send(&public_key, Payload::Scroll(event)).await {
+                Some(true) => METRICS.scroll_sent.inc(&address),
+                _ => {
+                    buffer.push(event);
+                }
+            }
+            continue;
+        }
+
         state.store(STATE_CONNECTING, Ordering::Relaxed);
 
         let mut client = tokio::select! {
@@ -133,6 +262,11 @@ async fn run_peer_connection(
                     Err(e) => {
                         error(&e);
                         state.store(STATE_DISCONNECTED, Ordering::Relaxed);
+                        if !disconnected {
+                            METRICS.scroll_reconnects.inc(&address);
+                            disconnected = true;
+                        }
+                        buffer.discard_deltas();
                         sleep(Duration::from_millis(RECONNECT_DELAY_MS)).await;
                         continue;
                     }
@@ -141,7 +275,10 @@ async fn run_peer_connection(
         };
 
         let (stream_tx, stream_rx) = mpsc::channel(CHANNEL_CAPACITY);
-        let out_stream = ReceiverStream::new(stream_rx);
+        let mut out_stream = Request::new(ReceiverStream::new(stream_rx));
+        if let Ok(source) = MetadataValue::try_from(host_public_key.as_str()) {
+            out_stream.metadata_mut().insert(SOURCE_METADATA_KEY, source);
+        }
 
         let mut rpc_handle = tokio::spawn({
             let address = address.clone();
@@ -172,45 +309,21 @@ async fn run_peer_connection(
                 _ = &mut rpc_handle => {
                     break;
                 }
-                result = rx.recv() => {
-                    match result {
-                        Some(event) => event,
-                        None => {
-                            rpc_handle.abort();
-                            return;
-                        }
-                    }
-                }
+                event = buffer.pop(&mut last_flush) => event,
             };
 
             if stream_tx.send(event).await.is_err() {
+                METRICS.scroll_dropped.inc(&address);
                 state.store(STATE_DISCONNECTED, Ordering::Relaxed);
                 break;
             }
+            METRICS.scroll_sent.inc(&address);
         }
 
         info!("Scroll connection lost to {}, reconnecting...", address);
+        METRICS.scroll_reconnects.inc(&address);
+        disconnected = true;
+        buffer.discard_deltas();
         sleep(Duration::from_millis(RECONNECT_DELAY_MS)).await;
     }
 }
-
-async fn connect(address: &str) -> Result<SynqServiceClient<Channel>> {
-    let host_port = match address.find('@') {
-        Some(i) => &address[i + 1..],
-        None => address,
-    };
-
-    let url = format!("http://{}", host_port);
-
-    let channel = Channel::from_shared(url)
-        .map_err(|e| Error::wrap(e, ErrorKind::Network)
-            .with_msg("transport: Invalid peer address")
-            .with_ctx("address", address))?
-        .connect()
-        .await
-        .map_err(|e| Error::wrap(e, ErrorKind::Network)
-            .with_msg("transport: Failed to connect to peer")
-            .with_ctx("address", address))?;
-
-    Ok(SynqServiceClient::new(channel))
-}
@@ -1,7 +1,11 @@
-use std::sync::Arc;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use tokio_stream::StreamExt;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_stream::{Stream, StreamExt};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{
     transport::Server as TonicServer,
     Request,
@@ -10,61 +14,101 @@ use tonic::{
     Streaming,
 };
 
-use crate::errors::{error, warn, trace};
+use crate::errors::{error, info, warn, trace};
 use crate::errors::{Result, Error, ErrorKind};
 use crate::config::Config;
 use crate::crypto;
 use crate::crypto::KeyStore;
 use crate::clipboard;
+use crate::metrics::METRICS;
 use crate::utils;
 use crate::synq::{
     synq_service_server::{SynqService, SynqServiceServer},
-    ScrollEvent, ClipboardEvent, ActiveEvent, ActivateEvent, Empty,
+    session_message::Payload,
+    ScrollEvent, PointerEvent, KeyEvent, GestureEvent, ClipboardEvent, ActiveEvent, ActivateEvent,
+    ActiveSyncRequest, PingEvent, HelloEvent, SessionChallenge, SessionMessage, Empty,
 };
 
-use super::active::{ActiveState, send_active_state};
+use super::active::{ActiveState, ActiveDelivery};
+use super::session::{SessionRegistry, run_session, verify_session_proof};
+use super::hello::{CapabilityMap, local_hello, negotiate, record};
+use super::constants::{
+    SESSION_AUTH_TIMEOUT,
+    SESSION_CAPACITY,
+    MIN_PROTOCOL_VERSION,
+    SOURCE_METADATA_KEY,
+};
+
+type SessionStream = Pin<Box<
+    dyn Stream<Item = std::result::Result<SessionMessage, Status>> + Send
+>>;
 
+pub struct InjectSenders {
+    pub scroll: Option<std::sync::mpsc::SyncSender<ScrollEvent>>,
+    pub pointer: Option<std::sync::mpsc::SyncSender<PointerEvent>>,
+    pub key: Option<std::sync::mpsc::SyncSender<KeyEvent>>,
+    pub gesture: Option<std::sync::mpsc::SyncSender<GestureEvent>>,
+}
+
+#[derive(Clone)]
 pub struct TransportServer {
-    config: Config,
+    config: Arc<RwLock<Config>>,
     key_store: Arc<KeyStore>,
     last_set_clipboard: Arc<AtomicU64>,
     scroll_inject_tx: Option<std::sync::mpsc::SyncSender<ScrollEvent>>,
+    pointer_inject_tx: Option<std::sync::mpsc::SyncSender<PointerEvent>>,
+    key_inject_tx: Option<std::sync::mpsc::SyncSender<KeyEvent>>,
+    gesture_inject_tx: Option<std::sync::mpsc::SyncSender<GestureEvent>>,
     active_state: ActiveState,
+    active_delivery: ActiveDelivery,
+    sessions: SessionRegistry,
+    capabilities: CapabilityMap,
 }
 
 #[tonic::async_trait]
 impl SynqService for TransportServer {
+    type SessionStream = SessionStream;
+
     async fn scroll(
         &self,
         request: Request<Streaming<ScrollEvent>>,
     ) -> std::result::Result<Response<Empty>, Status> {
-        if !self.config.server.scroll_destination {
+        if !self.config().server.scroll_destination {
             return Err(Status::permission_denied("scroll destination not enabled"));
         }
 
-        let scroll_tx = match &self.scroll_inject_tx {
-            Some(tx) => tx.clone(),
-            None => return Err(Status::unavailable("scroll sender not initialized")),
+        if self.scroll_inject_tx.is_none() {
+            return Err(Status::unavailable("scroll sender not initialized"));
+        }
+
+        let remote = request.remote_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_default();
+
+        let source = request.metadata().get(SOURCE_METADATA_KEY)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let Some(source) = self.event_source(source) else {
+            warn!("Received scroll stream from unknown source: {}", remote);
+            return Err(Status::permission_denied("unknown scroll source"));
         };
 
+        // Metrics are labelled by the configured peer address on every path
+        let address = self.config().peers.into_iter()
+            .find(|p| p.public_key == source)
+            .map_or(remote, |p| p.address);
+
         trace!("Scroll connection established");
 
         let mut in_stream = request.into_inner();
         while let Some(result) = in_stream.next().await {
             match result {
                 Ok(evt) => {
-                    trace!(
-                        delta_x = evt.delta_x,
-                        delta_y = evt.delta_y,
-                        "Received scroll event",
-                    );
-
-                    if let Err(std::sync::mpsc::TrySendError::Disconnected(_)) =
-                        scroll_tx.try_send(evt)
-                    {
-                        let e = Error::new(ErrorKind::Network)
-                            .with_msg("transport: Scroll inject channel closed");
-                        error(&e);
+                    if !self.accepts_input(&source) {
+                        METRICS.scroll_dropped.inc(&address);
+                        continue;
+                    }
+                    if !self.handle_scroll_event(&address, evt) {
                         break;
                     }
                 }
@@ -85,15 +129,13 @@ impl SynqService for TransportServer {
         &self,
         request: Request<ClipboardEvent>,
     ) -> std::result::Result<Response<Empty>, Status> {
-        if !self.config.server.clipboard_destination {
+        if !self.config().server.clipboard_destination {
             return Err(Status::permission_denied("clipboard destination not enabled"));
         }
 
         let event = request.into_inner();
 
-        if let Err(e) = handle_clipboard_event(
-            &self.config, &self.key_store, &self.last_set_clipboard, event,
-        ).await {
+        if let Err(e) = self.handle_clipboard(event).await {
             error(&e);
             return Err(Status::internal("failed to handle clipboard event"));
         }
@@ -105,13 +147,14 @@ impl SynqService for TransportServer {
         &self,
         request: Request<ActivateEvent>,
     ) -> std::result::Result<Response<ActiveEvent>, Status> {
-        if !self.config.server.scroll_source {
+        let config = self.config();
+        if !config.server.scroll_source {
             return Err(Status::permission_denied("scroll source not enabled"));
         }
 
         let event = request.into_inner();
 
-        let peer = self.config.peers.iter()
+        let peer = config.peers.iter()
             .find(|p| p.public_key == event.peer);
 
         let peer = match peer {
@@ -127,13 +170,26 @@ impl SynqService for TransportServer {
             return Err(Status::permission_denied("peer is not a scroll destination"));
         }
 
+        // Window focus decides the active peer, report it back unchanged
+        if config.server.focus_activation {
+            trace!(peer = %event.peer, "Ignoring activate request, focus activation enabled");
+            return Ok(Response::new(self.active_state.current_event()));
+        }
+
         let new_peer = if event.state {
             peer.public_key.clone()
         } else {
-            self.config.server.public_key.clone()
+            config.server.public_key.clone()
         };
 
         let new_clock = self.active_state.increment_and_set(new_peer.clone());
+        let active = ActiveEvent {
+            peer: new_peer.clone(),
+            clock: new_clock,
+            epoch: self.active_state.get_epoch(),
+            source: config.server.public_key.clone(),
+            ack_id: 0,
+        };
 
         trace!(
             peer = %new_peer,
@@ -142,26 +198,21 @@ impl SynqService for TransportServer {
             "Active peer updated via request",
         );
 
-        for dest_peer in &self.config.peers {
+        for dest_peer in &config.peers {
             if dest_peer.scroll_destination && dest_peer.public_key != new_peer {
                 tokio::spawn({
-                    let address = dest_peer.address.clone();
-                    let new_peer = new_peer.clone();
-                    let new_clock = new_clock;
+                    let delivery = self.active_delivery.clone();
+                    let dest_peer = dest_peer.clone();
+                    let active = active.clone();
 
                     async move {
-                        if let Err(e) = send_active_state(&address, &new_peer, new_clock).await {
-                            error(&e);
-                        }
+                        delivery.deliver(&dest_peer, active).await;
                     }
                 });
             }
         }
 
-        Ok(Response::new(ActiveEvent {
-            peer: new_peer,
-            clock: new_clock,
-        }))
+        Ok(Response::new(active))
     }
 
     async fn active_state(
@@ -170,97 +221,389 @@ impl SynqService for TransportServer {
     ) -> std::result::Result<Response<Empty>, Status> {
         let event = request.into_inner();
 
-        if event.clock == 0 {
-            self.active_state.reset();
-            trace!(
-                peer = %event.peer,
-                "Active state reset",
-            );
-            return Ok(Response::new(Empty {}));
-        }
+        let Some(source) = self.event_source(&event.source) else {
+            warn!("Received active state from unknown source: {}", event.source);
+            return Err(Status::permission_denied("unknown scroll source"));
+        };
+        self.handle_active_state(&source, event);
 
-        let current_clock = self.active_state.get_clock();
-        if event.clock <= current_clock {
-            trace!(
-                peer = %event.peer,
-                event_clock = event.clock,
-                current_clock = current_clock,
-                "Ignoring stale active state",
-            );
-            return Ok(Response::new(Empty {}));
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn sync_active_state(
+        &self,
+        request: Request<ActiveSyncRequest>,
+    ) -> std::result::Result<Response<ActiveEvent>, Status> {
+        let config = self.config();
+        if !config.server.scroll_source {
+            return Err(Status::permission_denied("scroll source not enabled"));
         }
 
-        self.active_state.set_active(event.peer.clone(), event.clock);
+        let event = request.into_inner();
 
-        trace!(
-            peer = %event.peer,
-            clock = event.clock,
-            "Active state updated",
-        );
+        let known = config.peers.iter()
+            .any(|p| p.public_key == event.peer && p.scroll_destination);
+        if !known {
+            warn!("Received active state sync from unknown destination: {}", event.peer);
+            return Err(Status::permission_denied("unknown destination peer"));
+        }
 
-        Ok(Response::new(Empty {}))
+        Ok(Response::new(self.active_state.current_event()))
     }
-}
 
-async fn handle_clipboard_event(
-    config: &Config,
-    key_store: &KeyStore,
-    last_set_clipboard: &AtomicU64,
-    event: ClipboardEvent,
-) -> Result<()> {
-    let peer = config.peers.iter()
-        .find(|p| p.public_key == event.client);
-
-    let peer = match peer {
-        Some(p) => p,
-        None => {
-            warn!("Received clipboard event from unknown: {}", event.client);
-            return Ok(());
+    async fn ping(
+        &self,
+        request: Request<PingEvent>,
+    ) -> std::result::Result<Response<PingEvent>, Status> {
+        let receive_time = utils::unix_time_us();
+        let event = request.into_inner();
+
+        let known = self.config.read().unwrap().peers.iter()
+            .any(|p| p.public_key == event.peer);
+        if !known {
+            warn!("Received ping from unknown peer: {}", event.peer);
+            return Err(Status::permission_denied("unknown peer"));
         }
-    };
 
-    if !peer.clipboard_source {
-        warn!("Received clipboard event from unauthorized: {}", event.client);
-        return Ok(());
+        Ok(Response::new(PingEvent {
+            peer: self.config.read().unwrap().server.public_key.clone(),
+            sequence: event.sequence,
+            send_time: event.send_time,
+            receive_time,
+            reply_time: utils::unix_time_us(),
+        }))
     }
 
-    let ciphertext = String::from_utf8(event.data)
-        .map_err(|e| Error::wrap(e, ErrorKind::Parse)
-            .with_msg("transport: Invalid UTF-8 in clipboard ciphertext"))?;
+    async fn session(
+        &self,
+        request: Request<Streaming<SessionMessage>>,
+    ) -> std::result::Result<Response<Self::SessionStream>, Status> {
+        let mut inbound = request.into_inner();
+
+        let open = match inbound.next().await {
+            Some(Ok(SessionMessage { payload: Some(Payload::Open(open)) })) => open,
+            Some(Err(e)) => {
+                let e = Error::wrap(e, ErrorKind::Network)
+                    .with_msg("transport: Failed to read session open");
+                error(&e);
+                return Err(Status::invalid_argument("failed to read session open"));
+            }
+            _ => return Err(Status::invalid_argument("expected session open")),
+        };
+
+        let peer = self.config().peers.into_iter()
+            .find(|p| p.public_key == open.peer);
+        let Some(peer) = peer else {
+            warn!("Received session from unknown peer: {}", open.peer);
+            return Err(Status::permission_denied("unknown peer"));
+        };
+
+        let Some(hello) = open.hello else {
+            warn!("Received session without hello from {}", peer.address);
+            return Err(Status::failed_precondition(
+                "session open is missing hello, upgrade synq on the peer"));
+        };
+
+        // The key in the open is only a claim, the peer has to seal the
+        // challenge with it before the session is registered
+        let challenge = crypto::generate_challenge();
+        let (tx, rx) = mpsc::channel(SESSION_CAPACITY);
+        let _ = tx.try_send(SessionMessage {
+            payload: Some(Payload::Challenge(SessionChallenge { nonce: challenge.clone() })),
+        });
+
+        tokio::spawn({
+            let handler = self.clone();
+            let sessions = self.sessions.clone();
+            let cancel = self.sessions.child_token();
+
+            async move {
+                let auth = timeout(Duration::from_millis(SESSION_AUTH_TIMEOUT), inbound.next()).await;
+                let verified = matches!(
+                    auth,
+                    Ok(Some(Ok(SessionMessage { payload: Some(Payload::Auth(auth)) })))
+                        if verify_session_proof(
+                            &handler.key_store, &peer.public_key, &challenge, &auth.proof),
+                );
+                if !verified {
+                    warn!("Session from {} failed authentication", peer.address);
+                    return;
+                }
+
+                if handler.handle_hello(&peer.address, &peer.public_key, &hello).is_err() {
+                    return;
+                }
 
-    let plaintext = crypto::decrypt(
-        key_store,
-        &peer.public_key,
-        &ciphertext,
-    )?;
+                info!("Session accepted from {}", peer.address);
 
-    trace!("Received clipboard from peer {}", peer.address);
+                if tx.send(SessionMessage {
+                    payload: Some(Payload::Hello(local_hello(&handler.public_key()))),
+                }).await.is_err() {
+                    return;
+                }
+                let id = sessions.register(&peer.public_key, tx.clone());
 
-    last_set_clipboard.store(utils::mono_time_ms(), Ordering::SeqCst);
-    clipboard::set_clipboard(plaintext);
+                run_session(peer, id, inbound, tx, handler, sessions, cancel).await;
+            }
+        });
+
+        let stream = ReceiverStream::new(rx).map(Ok);
+        Ok(Response::new(Box::pin(stream) as Self::SessionStream))
+    }
 
-    Ok(())
+    async fn hello(
+        &self,
+        request: Request<HelloEvent>,
+    ) -> std::result::Result<Response<HelloEvent>, Status> {
+        let event = request.into_inner();
+
+        let peer = self.config().peers.into_iter()
+            .find(|p| p.public_key == event.peer);
+        let Some(peer) = peer else {
+            warn!("Received hello from unknown peer: {}", event.peer);
+            return Err(Status::permission_denied("unknown peer"));
+        };
+
+        self.handle_hello(&peer.address, &peer.public_key, &event)?;
+
+        Ok(Response::new(local_hello(&self.public_key())))
+    }
 }
 
 impl TransportServer {
     pub fn new(
-        config: Config,
+        config: Arc<RwLock<Config>>,
         key_store: Arc<KeyStore>,
         last_set_clipboard: Arc<AtomicU64>,
-        scroll_inject_tx: Option<std::sync::mpsc::SyncSender<ScrollEvent>>,
+        inject_tx: InjectSenders,
         active_state: ActiveState,
+        sessions: SessionRegistry,
+        capabilities: CapabilityMap,
     ) -> Self {
         Self {
             config,
             key_store,
             last_set_clipboard,
-            scroll_inject_tx,
+            scroll_inject_tx: inject_tx.scroll,
+            pointer_inject_tx: inject_tx.pointer,
+            key_inject_tx: inject_tx.key,
+            gesture_inject_tx: inject_tx.gesture,
+            active_delivery: ActiveDelivery::new(
+                sessions.clone(),
+                capabilities.clone(),
+                active_state.clone(),
+            ),
             active_state,
+            sessions,
+            capabilities,
         }
     }
 
+    fn config(&self) -> Config {
+        self.config.read().unwrap().clone()
+    }
+
+    pub(crate) fn key_store(&self) -> &KeyStore {
+        &self.key_store
+    }
+
+    pub(crate) fn public_key(&self) -> String {
+        self.config.read().unwrap().server.public_key.clone()
+    }
+
+    pub(crate) fn handle_hello(
+        &self,
+        address: &str,
+        public_key: &str,
+        event: &HelloEvent,
+    ) -> std::result::Result<(), Status> {
+        match negotiate(event) {
+            Ok(caps) => {
+                record(&self.capabilities, address, public_key, caps);
+                Ok(())
+            }
+            Err(e) => {
+                error(&e.with_ctx("address", address));
+                Err(Status::failed_precondition(format!(
+                    "incompatible protocol version {} from {}, minimum supported is {}",
+                    event.protocol_version, event.version, MIN_PROTOCOL_VERSION,
+                )))
+            }
+        }
+    }
+
+    // Resolves the scroll source peer an event came from. Peers from before
+    // multiple sources do not name themselves, which is only unambiguous with
+    // a single source configured.
+    fn event_source(&self, source: &str) -> Option<String> {
+        let config = self.config.read().unwrap();
+        let mut sources = config.peers.iter().filter(|p| p.scroll_source);
+
+        let peer = if source.is_empty() {
+            let first = sources.next()?;
+            if sources.next().is_some() {
+                return None;
+            }
+            first
+        } else {
+            sources.find(|p| p.public_key == source)?
+        };
+
+        Some(peer.public_key.clone())
+    }
+
+    // Without a lease, input is taken from the source whose state we follow,
+    // which lets a mirrored destination receive scroll while another is active
+    pub(crate) fn accepts_input(&self, source: &str) -> bool {
+        match self.active_state.lease_holder() {
+            Some(holder) => holder == source,
+            None => self.event_source("").as_deref() == Some(source)
+                || self.active_state.state_source() == source,
+        }
+    }
+
+    pub(crate) fn handle_scroll_event(&self, address: &str, evt: ScrollEvent) -> bool {
+        let Some(scroll_tx) = &self.scroll_inject_tx else {
+            return true;
+        };
+
+        trace!(
+            delta_x = evt.delta_x,
+            delta_y = evt.delta_y,
+            "Received scroll event",
+        );
+
+        match scroll_tx.try_send(evt) {
+            Ok(()) => METRICS.scroll_injected.inc(address),
+            Err(std::sync::mpsc::TrySendError::Full(_)) => {
+                METRICS.scroll_dropped.inc(address);
+            }
+            Err(std::sync::mpsc::TrySendError::Disconnected(_)) => {
+                let e = Error::new(ErrorKind::Network)
+                    .with_msg("transport: Scroll inject channel closed");
+                error(&e);
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub(crate) async fn handle_clipboard(&self, event: ClipboardEvent) -> Result<()> {
+        let config = self.config();
+        if !config.server.clipboard_destination {
+            warn!("Received clipboard event while clipboard destination disabled");
+            return Ok(());
+        }
+
+        let peer = config.peers.iter()
+            .find(|p| p.public_key == event.client);
+
+        let peer = match peer {
+            Some(p) => p,
+            None => {
+                warn!("Received clipboard event from unknown: {}", event.client);
+                return Ok(());
+            }
+        };
+
+        if !peer.clipboard_source {
+            warn!("Received clipboard event from unauthorized: {}", event.client);
+            return Ok(());
+        }
+
+        METRICS.clipboard_received_bytes.inc_by(&peer.address, event.data.len() as u64);
+
+        let ciphertext = String::from_utf8(event.data)
+            .map_err(|e| Error::wrap(e, ErrorKind::Parse)
+                .with_msg("transport: Invalid UTF-8 in clipboard ciphertext"))?;
+
+        let plaintext = crypto::decrypt(
+            &self.key_store,
+            &peer.public_key,
+            &ciphertext,
+        ).inspect_err(|_| METRICS.encryption_failures.inc("decrypt"))?;
+
+        trace!("Received clipboard from peer {}", peer.address);
+
+        self.last_set_clipboard.store(utils::mono_time_ms(), Ordering::SeqCst);
+        clipboard::set_clipboard(plaintext);
+
+        Ok(())
+    }
+
+    pub(crate) fn handle_pointer_event(&self, address: &str, evt: PointerEvent) {
+        let Some(pointer_tx) = &self.pointer_inject_tx else {
+            return;
+        };
+
+        trace!(
+            dx = evt.dx,
+            dy = evt.dy,
+            button = evt.button,
+            pressed = evt.pressed,
+            "Received pointer event",
+        );
+
+        if let Err(std::sync::mpsc::TrySendError::Full(_)) = pointer_tx.try_send(evt) {
+            METRICS.pointer_dropped.inc(address);
+        }
+    }
+
+    pub(crate) fn handle_key_event(&self, address: &str, evt: KeyEvent) {
+        let Some(key_tx) = &self.key_inject_tx else {
+            return;
+        };
+
+        trace!(code = evt.code, value = evt.value, "Received key event");
+
+        if let Err(std::sync::mpsc::TrySendError::Full(_)) = key_tx.try_send(evt) {
+            METRICS.key_dropped.inc(address);
+        }
+    }
+
+    pub(crate) fn handle_gesture_event(&self, address: &str, evt: GestureEvent) {
+        let Some(gesture_tx) = &self.gesture_inject_tx else {
+            return;
+        };
+
+        trace!(
+            kind = evt.kind().as_str_name(),
+            phase = evt.phase().as_str_name(),
+            fingers = evt.fingers,
+            "Received gesture event",
+        );
+
+        if let Err(std::sync::mpsc::TrySendError::Full(_)) = gesture_tx.try_send(evt) {
+            METRICS.gesture_dropped.inc(address);
+        }
+    }
+
+    pub(crate) fn handle_active_state(&self, source: &str, event: ActiveEvent) {
+        if !self.active_state.apply(source, &event) {
+            trace!(
+                source = source,
+                peer = %event.peer,
+                event_clock = event.clock,
+                event_epoch = event.epoch,
+                current_clock = self.active_state.get_clock(),
+                lease = ?self.active_state.lease_holder(),
+                "Active state not applied",
+            );
+            return;
+        }
+
+        trace!(
+            source = source,
+            peer = %event.peer,
+            clock = event.clock,
+            epoch = event.epoch,
+            "Active state updated",
+        );
+    }
+
     pub async fn run(self) -> Result<()> {
-        let addr = self.config.server.bind.parse()
+        let addr = self.config().server.bind.parse()
             .map_err(|e| Error::wrap(e, ErrorKind::Parse)
                 .with_msg("transport: Failed to parse bind address"))?;
 
@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep, timeout, Duration};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tonic::Streaming;
+
+use crate::errors::{error, info, trace, warn};
+use crate::errors::{Result, Error, ErrorKind};
+use crate::config::PeerConfig;
+use crate::crypto;
+use crate::crypto::KeyStore;
+use crate::synq::{
+    session_message::Payload,
+    ActiveEvent, PingEvent, SessionAuth, SessionMessage, SessionOpen,
+};
+use crate::metrics::METRICS;
+use crate::utils;
+
+use super::client::connect;
+use super::server::TransportServer;
+use super::hello::local_hello;
+use super::constants::{SESSION_AUTH_TIMEOUT, SESSION_CAPACITY, SESSION_RECONNECT_DELAY};
+
+type PongMap = HashMap<(String, u64), oneshot::Sender<PingEvent>>;
+type AckMap = HashMap<(String, u64), oneshot::Sender<()>>;
+
+#[derive(Clone)]
+struct SessionHandle {
+    id: u64,
+    tx: mpsc::Sender<SessionMessage>,
+}
+
+#[derive(Clone)]
+pub struct SessionRegistry {
+    sessions: Arc<RwLock<HashMap<String, SessionHandle>>>,
+    pongs: Arc<Mutex<PongMap>>,
+    acks: Arc<Mutex<AckMap>>,
+    next_id: Arc<AtomicU64>,
+    cancel: CancellationToken,
+}
+
+impl SessionRegistry {
+    pub fn new(cancel: CancellationToken) -> Self {
+        Self {
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            pongs: Arc::new(Mutex::new(HashMap::new())),
+            acks: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU64::new(1)),
+            cancel,
+        }
+    }
+
+    pub fn child_token(&self) -> CancellationToken {
+        self.cancel.child_token()
+    }
+
+    pub fn register(&self, peer: &str, tx: mpsc::Sender<SessionMessage>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.sessions.write().unwrap()
+            .insert(peer.to_string(), SessionHandle { id, tx });
+        id
+    }
+
+    pub fn unregister(&self, peer: &str, id: u64) {
+        let mut sessions = self.sessions.write().unwrap();
+        if sessions.get(peer).is_some_and(|s| s.id == id) {
+            sessions.remove(peer);
+        }
+    }
+
+    pub fn has_session(&self, peer: &str) -> bool {
+        self.sessions.read().unwrap().contains_key(peer)
+    }
+
+    fn sender(&self, peer: &str) -> Option<mpsc::Sender<SessionMessage>> {
+        self.sessions.read().unwrap().get(peer).map(|s| s.tx.clone())
+    }
+
+    pub fn blocking_send(&self, peer: &str, payload: Payload) -> Option<bool> {
+        let tx = self.sender(peer)?;
+        Some(tx.blocking_send(SessionMessage {
+            payload: Some(payload),
+        }).is_ok())
+    }
+
+    pub async fn send(&self, peer: &str, payload: Payload) -> Option<bool> {
+        let tx = self.sender(peer)?;
+        Some(tx.send(SessionMessage {
+            payload: Some(payload),
+        }).await.is_ok())
+    }
+
+    pub async fn ping(&self, peer: &str, event: PingEvent) -> Option<Result<PingEvent>> {
+        let key = (peer.to_string(), event.sequence);
+        let (tx, rx) = oneshot::channel();
+        self.pongs.lock().unwrap().insert(key.clone(), tx);
+
+        // Callers wrap this in a timeout, the entry must go when dropped
+        let _guard = scopeguard::guard(key, {
+            let pongs = self.pongs.clone();
+            move |key| {
+                pongs.lock().unwrap().remove(&key);
+            }
+        });
+
+        let sent = self.send(peer, Payload::Ping(event)).await;
+        if sent != Some(true) {
+            return sent.map(|_| Err(Error::new(ErrorKind::Connection)
+                .with_msg("transport: Session closed")));
+        }
+
+        Some(rx.await.map_err(|e| Error::wrap(e, ErrorKind::Connection)
+            .with_msg("transport: Session closed before pong")))
+    }
+
+    fn complete_pong(&self, peer: &str, event: PingEvent) {
+        let tx = self.pongs.lock().unwrap()
+            .remove(&(peer.to_string(), event.sequence));
+        if let Some(tx) = tx {
+            let _ = tx.send(event);
+        }
+    }
+
+    // Sync and delivery can send the same clock at once, so each send waits
+    // on its own id which the destination echoes back in the ack
+    pub async fn send_active_state(
+        &self,
+        peer: &str,
+        mut event: ActiveEvent,
+    ) -> Option<Result<()>> {
+        event.ack_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let key = (peer.to_string(), event.ack_id);
+        let (tx, rx) = oneshot::channel();
+        self.acks.lock().unwrap().insert(key.clone(), tx);
+
+        let _guard = scopeguard::guard(key, {
+            let acks = self.acks.clone();
+            move |key| {
+                acks.lock().unwrap().remove(&key);
+            }
+        });
+
+        let sent = self.send(peer, Payload::ActiveState(event)).await;
+        if sent != Some(true) {
+            return sent.map(|_| Err(Error::new(ErrorKind::Connection)
+                .with_msg("transport: Session closed")));
+        }
+
+        Some(rx.await.map_err(|e| Error::wrap(e, ErrorKind::Connection)
+            .with_msg("transport: Session closed before active state ack")))
+    }
+
+    fn complete_ack(&self, peer: &str, event: ActiveEvent) {
+        let tx = self.acks.lock().unwrap()
+            .remove(&(peer.to_string(), event.ack_id));
+        if let Some(tx) = tx {
+            let _ = tx.send(());
+        }
+    }
+}
+
+pub(crate) async fn run_session(
+    peer: PeerConfig,
+    id: u64,
+    mut inbound: Streaming<SessionMessage>,
+    tx: mpsc::Sender<SessionMessage>,
+    handler: TransportServer,
+    sessions: SessionRegistry,
+    cancel: CancellationToken,
+) {
+    loop {
+        let result = tokio::select! {
+            _ = cancel.cancelled() => break,
+            result = inbound.next() => result,
+        };
+
+        let message = match result {
+            Some(Ok(message)) => message,
+            Some(Err(e)) => {
+                let e = Error::wrap(e, ErrorKind::Network)
+                    .with_msg("transport: Failed to read session message")
+                    .with_ctx("address", &peer.address);
+                error(&e);
+                break;
+            }
+            None => break,
+        };
+
+        let Some(payload) = message.payload else {
+            continue;
+        };
+
+        match payload {
+            Payload::Open(_) | Payload::Challenge(_) | Payload::Auth(_) => {
+                trace!(address = &peer.address, "Ignoring repeated session handshake");
+            }
+            Payload::Scroll(event) => {
+                if !peer.scroll_source {
+                    warn!("Received session scroll from non-source peer: {}", peer.address);
+                    continue;
+                }
+                if !handler.accepts_input(&peer.public_key) {
+                    METRICS.scroll_dropped.inc(&peer.address);
+                    continue;
+                }
+                if !handler.handle_scroll_event(&peer.address, event) {
+                    break;
+                }
+            }
+            Payload::Clipboard(event) => {
+                if let Err(e) = handler.handle_clipboard(event).await {
+                    error(&e);
+                }
+            }
+            Payload::ActiveState(event) => {
+                if !peer.scroll_source {
+                    warn!("Received session active state from non-source peer: {}",
+                        peer.address);
+                    continue;
+                }
+                handler.handle_active_state(&peer.public_key, event.clone());
+                if tx.send(SessionMessage {
+                    payload: Some(Payload::ActiveAck(event)),
+                }).await.is_err() {
+                    break;
+                }
+            }
+            Payload::ActiveAck(event) => {
+                sessions.complete_ack(&peer.public_key, event);
+            }
+            Payload::Ping(event) => {
+                let receive_time = utils::unix_time_us();
+                let pong = PingEvent {
+                    peer: handler.public_key(),
+                    sequence: event.sequence,
+                    send_time: event.send_time,
+                    receive_time,
+                    reply_time: utils::unix_time_us(),
+                };
+                if tx.send(SessionMessage {
+                    payload: Some(Payload::Pong(pong)),
+                }).await.is_err() {
+                    break;
+                }
+            }
+            Payload::Pong(event) => {
+                sessions.complete_pong(&peer.public_key, event);
+            }
+            Payload::Pointer(event) => {
+                if !peer.scroll_source {
+                    warn!("Received session pointer from non-source peer: {}", peer.address);
+                    continue;
+                }
+                if !handler.accepts_input(&peer.public_key) {
+                    METRICS.pointer_dropped.inc(&peer.address);
+                    continue;
+                }
+                handler.handle_pointer_event(&peer.address, event);
+            }
+            Payload::Key(event) => {
+                if !peer.scroll_source {
+                    warn!("Received session key from non-source peer: {}", peer.address);
+                    continue;
+                }
+                if !handler.accepts_input(&peer.public_key) {
+                    METRICS.key_dropped.inc(&peer.address);
+                    continue;
+                }
+                handler.handle_key_event(&peer.address, event);
+            }
+            Payload::Gesture(event) => {
+                if !peer.scroll_source {
+                    warn!("Received session gesture from non-source peer: {}", peer.address);
+                    continue;
+                }
+                if !handler.accepts_input(&peer.public_key) {
+                    METRICS.gesture_dropped.inc(&peer.address);
+                    continue;
+                }
+                handler.handle_gesture_event(&peer.address, event);
+            }
+            Payload::Hello(event) => {
+                if handler.handle_hello(&peer.address, &peer.public_key, &event).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    sessions.unregister(&peer.public_key, id);
+}
+
+#[derive(Clone)]
+pub struct SessionTransport {
+    peers: Arc<Mutex<Vec<SessionPeer>>>,
+    input_sharing: bool,
+    handler: TransportServer,
+    sessions: SessionRegistry,
+    cancel: CancellationToken,
+}
+
+struct SessionPeer {
+    peer: PeerConfig,
+    cancel: CancellationToken,
+}
+
+impl SessionTransport {
+    pub fn start(
+        peers: &[PeerConfig],
+        input_sharing: bool,
+        handler: TransportServer,
+        sessions: SessionRegistry,
+        cancel: CancellationToken,
+    ) -> Self {
+        let transport = Self {
+            peers: Arc::new(Mutex::new(Vec::new())),
+            input_sharing,
+            handler,
+            sessions,
+            cancel,
+        };
+        transport.update_peers(peers);
+        transport
+    }
+
+    pub fn update_peers(&self, peers: &[PeerConfig]) {
+        let mut session_peers = self.peers.lock().unwrap();
+
+        let wants_session = |p: &PeerConfig| {
+            p.session || (self.input_sharing && p.scroll_destination)
+        };
+
+        session_peers.retain(|sp| {
+            let keep = peers.iter().any(|p| wants_session(p) && *p == sp.peer);
+            if !keep {
+                info!("Stopping session to {}", sp.peer.address);
+                sp.cancel.cancel();
+            }
+            keep
+        });
+
+        for peer in peers.iter().filter(|p| wants_session(p)) {
+            if session_peers.iter().any(|sp| sp.peer == *peer) {
+                continue;
+            }
+
+            let cancel = self.cancel.child_token();
+
+            tokio::spawn({
+                let peer = peer.clone();
+                let handler = self.handler.clone();
+                let sessions = self.sessions.clone();
+                let cancel = cancel.clone();
+
+                async move {
+                    run_session_connection(peer, handler, sessions, cancel).await;
+                }
+            });
+
+            session_peers.push(SessionPeer {
+                peer: peer.clone(),
+                cancel,
+            });
+        }
+    }
+}
+
+async fn run_session_connection(
+    peer: PeerConfig,
+    handler: TransportServer,
+    sessions: SessionRegistry,
+    cancel: CancellationToken,
+) {
+    loop {
+        let result = tokio::select! {
+            _ = cancel.cancelled() => return,
+            result = open_session(&peer, &handler) => result,
+        };
+
+        match result {
+            Ok((tx, inbound)) => {
+                info!("Session established to {}", peer.address);
+
+                let id = sessions.register(&peer.public_key, tx.clone());
+                run_session(
+                    peer.clone(),
+                    id,
+                    inbound,
+                    tx,
+                    handler.clone(),
+                    sessions.clone(),
+                    cancel.clone(),
+                ).await;
+
+                if cancel.is_cancelled() {
+                    return;
+                }
+                info!("Session lost to {}, reconnecting...", peer.address);
+            }
+            Err(e) => error(&e),
+        }
+
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = sleep(Duration::from_millis(SESSION_RECONNECT_DELAY)) => {}
+        }
+    }
+}
+
+async fn open_session(
+    peer: &PeerConfig,
+    handler: &TransportServer,
+) -> Result<(mpsc::Sender<SessionMessage>, Streaming<SessionMessage>)> {
+    let mut client = connect(&peer.address).await?;
+
+    let (tx, rx) = mpsc::channel(SESSION_CAPACITY);
+    tx.send(SessionMessage {
+        payload: Some(Payload::Open(SessionOpen {
+            peer: handler.public_key(),
+            hello: Some(local_hello(&handler.public_key())),
+        })),
+    })
+        .await
+        .map_err(|e| Error::wrap(e, ErrorKind::Network)
+            .with_msg("transport: Failed to queue session open"))?;
+
+    let mut inbound = client.session(ReceiverStream::new(rx))
+        .await
+        .map_err(|e| Error::wrap(e, ErrorKind::Network)
+            .with_msg("transport: Failed to open session")
+            .with_ctx("address", &peer.address))?
+        .into_inner();
+
+    let challenge = match timeout(Duration::from_millis(SESSION_AUTH_TIMEOUT), inbound.next()).await {
+        Ok(Some(Ok(SessionMessage { payload: Some(Payload::Challenge(challenge)) }))) => challenge,
+        _ => {
+            return Err(Error::new(ErrorKind::Unauthorized)
+                .with_msg("transport: Peer sent no session challenge")
+                .with_ctx("address", &peer.address));
+        }
+    };
+
+    let proof = session_proof(
+        handler.key_store(),
+        &handler.public_key(),
+        &peer.public_key,
+        &challenge.nonce,
+    )?;
+    tx.send(SessionMessage {
+        payload: Some(Payload::Auth(SessionAuth { proof })),
+    })
+        .await
+        .map_err(|e| Error::wrap(e, ErrorKind::Network)
+            .with_msg("transport: Failed to queue session auth"))?;
+
+    Ok((tx, inbound))
+}
+
+// The opener seals the challenge together with its own key, so a proof a
+// host made for a session it opened cannot be replayed back at it
+pub(crate) fn session_proof(
+    key_store: &KeyStore,
+    public_key: &str,
+    peer_public_key: &str,
+    challenge: &str,
+) -> Result<String> {
+    crypto::encrypt(key_store, peer_public_key, &format!("{} {}", public_key, challenge))
+}
+
+pub(crate) fn verify_session_proof(
+    key_store: &KeyStore,
+    peer_public_key: &str,
+    challenge: &str,
+    proof: &str,
+) -> bool {
+    crypto::decrypt(key_store, peer_public_key, proof)
+        .is_ok_and(|p| p == format!("{} {}", peer_public_key, challenge))
+}
@@ -1,22 +1,32 @@
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock as StdRwLock};
 
 use tokio::sync::{mpsc, RwLock};
 use tokio_util::sync::CancellationToken;
 
 use crate::errors::{error, info, warn};
 use crate::errors::{Result, Error, ErrorKind};
-use crate::config::Config;
+use crate::config::{Config, PeerConfig};
 use crate::crypto::KeyStore;
-use crate::synq::ScrollEvent;
+use crate::metrics::METRICS;
+use crate::synq::{ScrollEvent, PointerEvent, KeyEvent, GestureEvent, session_message::Payload};
 
-use super::server::TransportServer;
+use super::server::{TransportServer, InjectSenders};
 use super::scroll::ScrollTransport;
 use super::clipboard::{ClipboardTransport, ClipboardSendEvent};
-use super::active::{ActiveState, ActiveTransport, ActiveRequestEvent};
+use super::active::{ActiveState, ActiveTransport, ActiveRequestEvent, ActiveDelivery, next_epoch, run_active_sync};
+use super::heartbeat::{HeartbeatTransport, PeerHealth, PeerHealthMap};
+use super::session::{SessionRegistry, SessionTransport};
+use super::coalesce::ScrollBuffer;
+use super::hello::{CapabilityMap, PeerCapabilities};
+use super::constants::{FEATURE_POINTER, FEATURE_KEYBOARD, FEATURE_GESTURE};
 
 const SCROLL_INJECT_CAPACITY: usize = 32;
+const POINTER_INJECT_CAPACITY: usize = 128;
+const KEY_INJECT_CAPACITY: usize = 128;
+const GESTURE_INJECT_CAPACITY: usize = 128;
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -29,6 +39,8 @@ pub enum PeerState {
 #[allow(dead_code)]
 pub struct TransportStatus {
     peers: RwLock<Vec<(String, Arc<AtomicU8>)>>,
+    health: PeerHealthMap,
+    capabilities: CapabilityMap,
     server_running: AtomicBool,
 }
 
@@ -36,6 +48,8 @@ impl TransportStatus {
     fn new() -> Self {
         Self {
             peers: RwLock::new(Vec::new()),
+            health: Arc::new(StdRwLock::new(HashMap::new())),
+            capabilities: Arc::new(StdRwLock::new(HashMap::new())),
             server_running: AtomicBool::new(false),
         }
     }
@@ -43,15 +57,24 @@ impl TransportStatus {
 
 #[derive(Clone)]
 pub struct Transport {
-    scroll_tx: mpsc::Sender<ScrollEvent>,
+    scroll_buffer: ScrollBuffer,
     clipboard_tx: mpsc::Sender<ClipboardSendEvent>,
     active_tx: mpsc::Sender<ActiveRequestEvent>,
     pub active_state: ActiveState,
-    #[allow(dead_code)]
+    active_delivery: ActiveDelivery,
+    config: Arc<StdRwLock<Config>>,
+    scroll: ScrollTransport,
+    heartbeat: HeartbeatTransport,
+    session: SessionTransport,
+    sessions: SessionRegistry,
     status: Arc<TransportStatus>,
     last_set_clipboard: Arc<AtomicU64>,
     cancel: CancellationToken,
     scroll_inject_rx: Arc<Mutex<Option<ScrollInjectRx>>>,
+    pointer_inject_rx: Arc<Mutex<Option<PointerInjectRx>>>,
+    key_inject_rx: Arc<Mutex<Option<KeyInjectRx>>>,
+    gesture_inject_rx: Arc<Mutex<Option<GestureInjectRx>>>,
+    runtime: tokio::runtime::Handle,
 }
 
 pub struct ScrollInjectRx {
@@ -62,6 +85,43 @@ impl ScrollInjectRx {
     pub fn recv(&self) -> Option<ScrollEvent> {
         self.rx.recv().ok()
     }
+
+    pub fn recv_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> std::result::Result<ScrollEvent, std::sync::mpsc::RecvTimeoutError> {
+        self.rx.recv_timeout(timeout)
+    }
+}
+
+pub struct PointerInjectRx {
+    rx: std::sync::mpsc::Receiver<PointerEvent>,
+}
+
+impl PointerInjectRx {
+    pub fn recv(&self) -> Option<PointerEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+pub struct KeyInjectRx {
+    rx: std::sync::mpsc::Receiver<KeyEvent>,
+}
+
+impl KeyInjectRx {
+    pub fn recv(&self) -> Option<KeyEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+pub struct GestureInjectRx {
+    rx: std::sync::mpsc::Receiver<GestureEvent>,
+}
+
+impl GestureInjectRx {
+    pub fn recv(&self) -> Option<GestureEvent> {
+        self.rx.recv().ok()
+    }
 }
 
 impl Transport {
@@ -80,27 +140,83 @@ impl Transport {
             (None, None)
         };
 
+        let accept_pointer = config.server.scroll_destination && config.server.pointer_sharing;
+        let (pointer_inject_rx, pointer_inject_tx) = if accept_pointer {
+            let (tx, rx) = std::sync::mpsc::sync_channel(POINTER_INJECT_CAPACITY);
+            (Some(PointerInjectRx { rx }), Some(tx))
+        } else {
+            (None, None)
+        };
+
+        let accept_key = config.server.scroll_destination && config.server.keyboard_sharing;
+        let (key_inject_rx, key_inject_tx) = if accept_key {
+            let (tx, rx) = std::sync::mpsc::sync_channel(KEY_INJECT_CAPACITY);
+            (Some(KeyInjectRx { rx }), Some(tx))
+        } else {
+            (None, None)
+        };
+
+        let accept_gesture = config.server.scroll_destination && config.server.gesture_sharing;
+        let (gesture_inject_rx, gesture_inject_tx) = if accept_gesture {
+            let (tx, rx) = std::sync::mpsc::sync_channel(GESTURE_INJECT_CAPACITY);
+            (Some(GestureInjectRx { rx }), Some(tx))
+        } else {
+            (None, None)
+        };
+
         let active_state = ActiveState::new(config.server.public_key.clone());
+        if config.server.scroll_source {
+            active_state.begin_epoch(next_epoch(config.path()).await?);
+        }
+        let shared_config = Arc::new(StdRwLock::new(config.clone()));
+        let sessions = SessionRegistry::new(cancel.clone());
+        let active_delivery = ActiveDelivery::new(
+            sessions.clone(),
+            status.capabilities.clone(),
+            active_state.clone(),
+        );
 
         let active_tx = ActiveTransport::start(
-            &config.peers,
+            shared_config.clone(),
             config.server.public_key.clone(),
             active_state.clone(),
+            status.health.clone(),
             cancel.clone(),
         );
 
+        if config.server.scroll_source {
+            tokio::spawn({
+                let delivery = active_delivery.clone();
+                let config = shared_config.clone();
+                let cancel = cancel.clone();
+
+                async move {
+                    run_active_sync(delivery, config, cancel).await;
+                }
+            });
+        }
+
+        let server = TransportServer::new(
+            shared_config.clone(),
+            key_store.clone(),
+            last_set_clipboard.clone(),
+            InjectSenders {
+                scroll: scroll_inject_tx,
+                pointer: pointer_inject_tx,
+                key: key_inject_tx,
+                gesture: gesture_inject_tx,
+            },
+            active_state.clone(),
+            sessions.clone(),
+            status.capabilities.clone(),
+        );
+
         let should_run_server = config.server.clipboard_destination
             || config.server.scroll_destination
             || config.server.scroll_source;
         if should_run_server {
-            let server = TransportServer::new(
-                config.clone(),
-                key_store.clone(),
-                last_set_clipboard.clone(),
-                scroll_inject_tx,
-                active_state.clone(),
-            );
             tokio::spawn({
+                let server = server.clone();
                 let status = status.clone();
 
                 async move {
@@ -115,41 +231,203 @@ impl Transport {
             });
         }
 
-        let (scroll_tx, peer_states) = ScrollTransport::start(
+        let (scroll, scroll_buffer) = ScrollTransport::start(
             &config.peers,
             active_state.clone(),
+            status.health.clone(),
+            sessions.clone(),
+            status.capabilities.clone(),
+            config.server.scroll_flush_interval,
             cancel.clone(),
         );
+        scroll.set_mirror(config.server.scroll_mirror);
         {
             let mut peers = status.peers.write().await;
-            *peers = peer_states;
+            *peers = scroll.peer_states();
         }
 
+        let heartbeat = HeartbeatTransport::start(
+            &config.peers,
+            config.server.public_key.clone(),
+            status.health.clone(),
+            sessions.clone(),
+            status.capabilities.clone(),
+            cancel.clone(),
+        );
+
+        let session = SessionTransport::start(
+            &config.peers,
+            (config.server.pointer_sharing
+                || config.server.keyboard_sharing
+                || config.server.gesture_sharing)
+                && config.server.scroll_source,
+            server,
+            sessions.clone(),
+            cancel.clone(),
+        );
+
         let clipboard_tx = ClipboardTransport::start(
             key_store.clone(),
             config.server.public_key.clone(),
+            sessions.clone(),
+            status.capabilities.clone(),
         );
 
         info!("Transport initialized");
 
         Ok(Self {
-            scroll_tx,
+            scroll_buffer,
             clipboard_tx,
             active_tx,
             active_state,
+            active_delivery,
+            config: shared_config,
+            scroll,
+            heartbeat,
+            session,
+            sessions,
             status,
             last_set_clipboard,
             cancel,
             scroll_inject_rx: Arc::new(Mutex::new(scroll_inject_rx)),
+            pointer_inject_rx: Arc::new(Mutex::new(pointer_inject_rx)),
+            key_inject_rx: Arc::new(Mutex::new(key_inject_rx)),
+            gesture_inject_rx: Arc::new(Mutex::new(gesture_inject_rx)),
+            runtime: tokio::runtime::Handle::current(),
         })
     }
 
-    pub fn send_scroll(&self, event: ScrollEvent) -> bool {
-        if let Err(e) = self.scroll_tx.try_send(event) {
-            warn!("transport: Dropped scroll event: {}", e);
+    pub async fn update_config(&self, config: &Config) {
+        *self.config.write().unwrap() = config.clone();
+
+        self.scroll.update_peers(&config.peers);
+        self.scroll.set_mirror(config.server.scroll_mirror);
+        self.heartbeat.update_peers(&config.peers);
+        self.session.update_peers(&config.peers);
+        let mut peers = self.status.peers.write().await;
+        *peers = self.scroll.peer_states();
+    }
+
+    fn active_peer_address(&self) -> String {
+        let Some(active_peer) = self.active_state.get_active_peer() else {
+            return String::new();
+        };
+
+        self.config.read().unwrap().peers.iter()
+            .find(|p| p.public_key == active_peer)
+            .map(|p| p.address.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn peers(&self) -> Vec<PeerConfig> {
+        self.config.read().unwrap().peers.clone()
+    }
+
+    pub fn send_scroll(&self, event: ScrollEvent) {
+        if self.scroll_buffer.push(event) {
+            METRICS.scroll_coalesced.inc(&self.active_peer_address());
+        }
+    }
+
+    fn input_target(&self, feature: &str) -> Option<String> {
+        let active_peer = self.active_state.get_active_peer()?;
+        if self.active_state.is_host_active() {
+            return None;
+        }
+
+        let supported = self.status.capabilities.read().unwrap().get(&active_peer)
+            .is_some_and(|c| c.supports(feature));
+        supported.then_some(active_peer)
+    }
+
+    pub fn send_pointer(&self, event: PointerEvent) -> bool {
+        let Some(active_peer) = self.input_target(FEATURE_POINTER) else {
+            return false;
+        };
+
+        match self.sessions.blocking_send(&active_peer, Payload::Pointer(event)) {
+            Some(true) => {
+                METRICS.pointer_sent.inc(&self.active_peer_address());
+                true
+            }
+            _ => {
+                METRICS.pointer_dropped.inc(&self.active_peer_address());
+                false
+            }
+        }
+    }
+
+    pub fn send_gesture(&self, event: GestureEvent) -> bool {
+        let Some(active_peer) = self.input_target(FEATURE_GESTURE) else {
             return false;
+        };
+
+        match self.sessions.blocking_send(&active_peer, Payload::Gesture(event)) {
+            Some(true) => {
+                METRICS.gesture_sent.inc(&self.active_peer_address());
+                true
+            }
+            _ => {
+                METRICS.gesture_dropped.inc(&self.active_peer_address());
+                false
+            }
         }
-        true
+    }
+
+    pub fn keyboard_target(&self) -> Option<String> {
+        self.input_target(FEATURE_KEYBOARD)
+            .filter(|peer| self.sessions.has_session(peer))
+    }
+
+    pub fn send_key(&self, peer: &str, event: KeyEvent) -> bool {
+        let address = self.peers().into_iter()
+            .find(|p| p.public_key == peer)
+            .map(|p| p.address)
+            .unwrap_or_default();
+
+        match self.sessions.blocking_send(peer, Payload::Key(event)) {
+            Some(true) => {
+                METRICS.key_sent.inc(&address);
+                true
+            }
+            _ => {
+                METRICS.key_dropped.inc(&address);
+                false
+            }
+        }
+    }
+
+    pub fn activate_peer(&self, public_key: Option<String>) {
+        let host_key = self.config.read().unwrap().server.public_key.clone();
+        let new_peer = public_key.unwrap_or(host_key.clone());
+
+        self.active_state.increment_and_set(new_peer.clone());
+        let event = self.active_state.current_event();
+
+        if new_peer == host_key {
+            info!("Active peer set to host");
+        } else {
+            info!("Active peer set to {}", self.active_peer_address());
+        }
+
+        for dest_peer in self.peers() {
+            if !dest_peer.scroll_destination {
+                continue;
+            }
+
+            self.runtime.spawn({
+                let delivery = self.active_delivery.clone();
+                let event = event.clone();
+
+                async move {
+                    delivery.deliver(&dest_peer, event).await;
+                }
+            });
+        }
+    }
+
+    pub fn active_delivery(&self) -> &ActiveDelivery {
+        &self.active_delivery
     }
 
     pub fn send_clipboard(
@@ -168,7 +446,6 @@ impl Transport {
         true
     }
 
-    #[allow(dead_code)]
     pub async fn status(&self) -> Vec<(String, PeerState)> {
         let peers = self.status.peers.read().await;
         peers.iter()
@@ -176,6 +453,21 @@ impl Transport {
             .collect()
     }
 
+    #[allow(dead_code)]
+    pub fn peer_health(&self, public_key: &str) -> Option<PeerHealth> {
+        self.status.health.read().unwrap().get(public_key).cloned()
+    }
+
+    #[allow(dead_code)]
+    pub fn peer_capabilities(&self, public_key: &str) -> Option<PeerCapabilities> {
+        self.status.capabilities.read().unwrap().get(public_key).cloned()
+    }
+
+    pub fn is_peer_down(&self, public_key: &str) -> bool {
+        self.status.health.read().unwrap().get(public_key)
+            .is_some_and(|h| h.is_down())
+    }
+
     #[allow(dead_code)]
     pub fn server_running(&self) -> bool {
         self.status.server_running.load(Ordering::Relaxed)
@@ -217,4 +509,16 @@ impl Transport {
     pub fn take_scroll_inject_rx(&self) -> Option<ScrollInjectRx> {
         self.scroll_inject_rx.lock().unwrap().take()
     }
+
+    pub fn take_pointer_inject_rx(&self) -> Option<PointerInjectRx> {
+        self.pointer_inject_rx.lock().unwrap().take()
+    }
+
+    pub fn take_key_inject_rx(&self) -> Option<KeyInjectRx> {
+        self.key_inject_rx.lock().unwrap().take()
+    }
+
+    pub fn take_gesture_inject_rx(&self) -> Option<GestureInjectRx> {
+        self.gesture_inject_rx.lock().unwrap().take()
+    }
 }
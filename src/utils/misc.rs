@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::LazyLock;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::errors::{Result, Error, ErrorKind};
 
@@ -24,11 +24,55 @@ pub fn filter_str(s: &str, n: usize) -> String {
         .collect()
 }
 
+// Case-insensitive glob match supporting * and ?
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 pub fn mono_time_ms() -> u64 {
     let start = START_TIME.get_or_init(Instant::now);
     start.elapsed().as_millis() as u64
 }
 
+pub fn mono_time_us() -> u64 {
+    let start = START_TIME.get_or_init(Instant::now);
+    start.elapsed().as_micros() as u64
+}
+
+pub fn unix_time_us() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as i64)
+        .unwrap_or(0)
+}
+
 pub fn get_config_path() -> Result<PathBuf> {
     let home = std::env::var("HOME")
         .map_err(|e| Error::wrap(e, ErrorKind::Parse)